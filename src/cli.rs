@@ -0,0 +1,330 @@
+/* cli module lets the password manager be run w/subcommands
+   (add, list, search, get, edit, rm, generate) so it can be used from
+   shell scripts. Running w/o a subcommand opens the menu instead. */
+
+pub mod cli {
+    use clap::{ArgGroup, Args, Parser, Subcommand};
+    use std::io::{self, Write};
+    use rusqlite::Connection;
+    use crate::records::records::{AccountId, AccountInfo, User, Login, Transfer, Retrieve, open_database, generate_password,
+        generate_passphrase, default_passphrase_words, PasswordSpecs, PassphraseSpecs};
+    use crate::records::records::Default as _; // records' Default would clash w/the one clap derives use
+    use crate::terminal::terminal::{read_secret, stdin_is_tty};
+    use crate::error::error::{Error, Result};
+    use crate::secret::secret::Secret;
+
+    #[derive(Parser)]
+    #[command(name = "password_manager", version, about = "Stores account passwords in an encrypted vault")]
+    pub struct Cli {
+        /// Username of the vault to open. Defaults to $PASSWORD_MANAGER_USER
+        #[arg(short, long, global = true)]
+        pub user: Option<String>,
+
+        /// Name (from the config file) or path of the vault to open. Defaults to
+        /// $PASSWORD_MANAGER_VAULT, then the config file's default, then the data directory
+        #[arg(long, global = true)]
+        pub vault: Option<String>,
+
+        #[command(subcommand)]
+        pub command: Option<Command>
+    }
+
+    #[derive(Subcommand)]
+    pub enum Command {
+        /// Add a new entry
+        // checked while parsing, so a missing password fails before the master password is read
+        #[command(group(ArgGroup::new("password_source").required(true).args(["password_stdin", "generate", "passphrase"])))]
+        Add {
+            /// Name of the account, such as a website
+            #[arg(short, long)]
+            account: String,
+            /// Username for the account
+            #[arg(short = 'n', long)]
+            username: String,
+            #[command(flatten)]
+            password: PasswordArgs
+        },
+        /// List all entries (without their passwords)
+        List,
+        /// List the entries whose name or username matches, best matches first
+        Search {
+            /// Part of an account name or username. Its letters only need to appear in order
+            query: String
+        },
+        /// Print the password of an entry
+        Get {
+            /// ID or name of the entry
+            entry: String
+        },
+        /// Change an entry. Anything not given is left as it is
+        Edit {
+            /// ID or name of the entry
+            entry: String,
+            /// New name of the account
+            #[arg(short, long)]
+            account: Option<String>,
+            /// New username for the account
+            #[arg(short = 'n', long)]
+            username: Option<String>,
+            #[command(flatten)]
+            password: PasswordArgs
+        },
+        /// Move an entry to the trash
+        Rm {
+            /// ID or name of the entry
+            entry: String,
+            /// Delete the entry for good instead of moving it to the trash
+            #[arg(long)]
+            purge: bool
+        },
+        /// Print a generated password without storing it
+        Generate {
+            #[command(flatten)]
+            generator: GeneratorArgs
+        }
+    }
+
+    // where the password of an added or edited entry comes from. It is never taken
+    // as an argument, since those show up in ps and shell history.
+    #[derive(Args)]
+    pub struct PasswordArgs {
+        /// Read the password to store from standard input (the line after the master
+        /// password, if that is read from there too), or prompt for it on a terminal
+        #[arg(short, long, conflicts_with_all = ["generate", "passphrase"])]
+        pub password_stdin: bool,
+        /// Store a generated password
+        #[arg(short, long)]
+        pub generate: bool,
+        #[command(flatten)]
+        pub generator: GeneratorArgs
+    }
+
+    #[derive(Args)]
+    pub struct GeneratorArgs {
+        /// Length of a generated password
+        #[arg(short, long, default_value_t = 20, value_parser = clap::value_parser!(u8).range(1..))]
+        pub length: u8,
+        /// Minimum number of lowercase letters
+        #[arg(long)]
+        pub lower: Option<u8>,
+        /// Minimum number of uppercase letters
+        #[arg(long)]
+        pub upper: Option<u8>,
+        /// Minimum number of numbers
+        #[arg(long)]
+        pub numbers: Option<u8>,
+        /// Minimum number of special characters
+        #[arg(long)]
+        pub special: Option<u8>,
+        /// Characters that are never used
+        #[arg(long, default_value = "")]
+        pub exclude: String,
+        /// Leave out characters that are easy to mix up (0/O, l/1)
+        #[arg(long)]
+        pub avoid_ambiguous: bool,
+        /// Generate a passphrase of words instead of a password
+        #[arg(long)]
+        pub passphrase: bool,
+        /// Number of words in a passphrase
+        #[arg(long, default_value_t = default_passphrase_words(), value_parser = clap::value_parser!(u8).range(1..))]
+        pub words: u8,
+        /// Separator between the words of a passphrase
+        #[arg(long, default_value = "-")]
+        pub separator: String,
+        /// Capitalize each word of a passphrase
+        #[arg(long)]
+        pub capitalize: bool,
+        /// Add a random number to one word of a passphrase
+        #[arg(long)]
+        pub digit: bool
+    }
+
+    impl GeneratorArgs {
+        fn generate(&self) -> Result<Secret> {
+            if self.passphrase {
+                let specs = PassphraseSpecs {
+                    words: self.words,
+                    separator: self.separator.clone(),
+                    capitalize: self.capitalize,
+                    add_digit: self.digit
+                };
+                return Ok(generate_passphrase(&specs).0);
+            }
+
+            let defaults = PasswordSpecs::default();
+            let specs = PasswordSpecs {
+                lower_letters: self.lower.unwrap_or(defaults.lower_letters),
+                upper_letters: self.upper.unwrap_or(defaults.upper_letters),
+                numbers: self.numbers.unwrap_or(defaults.numbers),
+                special_chars: self.special.unwrap_or(defaults.special_chars),
+                excluded_chars: self.exclude.clone(),
+                avoid_ambiguous: self.avoid_ambiguous
+            };
+            generate_password(self.length, &specs)
+                .map_err(|e| Error::InvalidInput(format!("password generation failed because {}", e)))
+        }
+    }
+
+    impl PasswordArgs {
+        // None means no new password was asked for
+        fn new_password(&self) -> Result<Option<Secret>> {
+            if self.password_stdin {
+                // stdout may be piped into another program, so the prompt goes to stderr
+                if stdin_is_tty() {
+                    eprint!("Password to store: ");
+                    io::stderr().flush()?;
+                }
+                match read_secret()? {
+                    password if password.is_empty() => Err(Error::InvalidInput(String::from("no password was entered"))),
+                    password => Ok(Some(password))
+                }
+            } else if self.generate || self.generator.passphrase {
+                self.generator.generate().map(Some)
+            } else {
+                Ok(None)
+            }
+        }
+    }
+
+    // runs a single subcommand and returns the exit code for the process
+    pub fn run(user_arg: Option<String>, command: Command) -> i32 {
+        match execute(user_arg, command) {
+            Ok(_) => 0,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                eprintln!("{}", e.advice());
+                e.exit_code()
+            }
+        }
+    }
+
+    fn execute(user_arg: Option<String>, command: Command) -> Result<()> {
+        // generating a password is the only command that does not need a vault
+        if let Command::Generate { generator } = &command {
+            println!("{}", generator.generate()?.expose());
+            return Ok(());
+        }
+
+        let conn = open_database()?;
+        let user = login(&conn, user_arg)?;
+        let id = user.get_id();
+        let key = user.get_vault_key();
+
+        match command {
+            Command::Add { account, username, password } => {
+                let password = password.new_password()?.expect("clap requires a password source for add");
+                let entry = AccountInfo { account, username, password, accountId: None, fields: Vec::new() };
+                AccountInfo::add_account(&conn, entry, &id, key)?;
+            }
+            Command::List => {
+                for account in fetch_accounts(&conn, &user)? {
+                    println!("{}\t{}\t{}", account.id()?, account.account, account.username);
+                }
+            }
+            Command::Search { query } => {
+                for account in user.find_accounts(&conn, &query)? {
+                    println!("{}\t{}\t{}", account.id()?, account.account, account.username);
+                }
+            }
+            Command::Get { entry } => {
+                let accounts = fetch_accounts(&conn, &user)?;
+                println!("{}", find_entry(&accounts, &entry)?.password.expose());
+            }
+            Command::Edit { entry, account, username, password } => {
+                let accounts = fetch_accounts(&conn, &user)?;
+                let current = find_entry(&accounts, &entry)?;
+                let updated = AccountInfo {
+                    account: account.unwrap_or_else(|| current.account.clone()),
+                    username: username.unwrap_or_else(|| current.username.clone()),
+                    password: password.new_password()?.unwrap_or_else(|| current.password.clone()),
+                    accountId: current.accountId,
+                    fields: current.fields.clone()
+                };
+                AccountInfo::update_account(&conn, updated, &id, key)?;
+            }
+            Command::Rm { entry, purge } => {
+                let accounts = fetch_accounts(&conn, &user)?;
+                let account_id = find_entry(&accounts, &entry)?.id()?;
+                AccountInfo::delete_account(&conn, &account_id, &id)?;
+                if purge {
+                    AccountInfo::purge_account(&conn, &account_id, &id)?;
+                }
+            }
+            Command::Generate { .. } => unreachable!("handled before logging in")
+        }
+        Ok(())
+    }
+
+    // the username comes from --user or $PASSWORD_MANAGER_USER and the master password
+    // from $PASSWORD_MANAGER_PASSWORD, or else the first line of standard input
+    fn login(conn: &Connection, user_arg: Option<String>) -> Result<User> {
+        let username = user_arg
+            .or_else(|| std::env::var("PASSWORD_MANAGER_USER").ok())
+            .ok_or_else(|| Error::InvalidInput(String::from("no user given; use --user or set PASSWORD_MANAGER_USER")))?;
+        let password = match std::env::var("PASSWORD_MANAGER_PASSWORD") {
+            Ok(password) => Secret::new(password),
+            Err(_) => {
+                // stdout may be piped into another program, so the prompt goes to stderr
+                if stdin_is_tty() {
+                    eprint!("Master password: ");
+                    io::stderr().flush()?;
+                }
+                read_secret()?
+            }
+        };
+
+        User::log_in(conn, &username, password.expose())
+    }
+
+    fn fetch_accounts(conn: &Connection, user: &User) -> Result<Vec<AccountInfo>> {
+        user.get_accounts(conn)
+    }
+
+    // an entry can be given by its ID or by its exact account name (ignoring case)
+    fn find_entry<'a>(accounts: &'a [AccountInfo], entry: &str) -> Result<&'a AccountInfo> {
+        if let Ok(id) = entry.parse::<AccountId>() {
+            if let Some(account) = accounts.iter().find(|account| account.accountId == Some(id)) {
+                return Ok(account);
+            }
+        }
+
+        let named: Vec<&AccountInfo> = accounts.iter().filter(|account| account.account.eq_ignore_ascii_case(entry)).collect();
+        match named.as_slice() {
+            [account] => Ok(account),
+            [] => Err(Error::NotFound(format!("entry named \"{}\"", entry))),
+            _ => Err(Error::InvalidInput(format!("several entries are named \"{}\"; use the ID from `list` instead", entry)))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn parse(args: &[&str]) -> std::result::Result<Cli, clap::Error> {
+            Cli::try_parse_from(std::iter::once("password_manager").chain(args.iter().copied()))
+        }
+
+        #[test]
+        fn passwords_are_not_taken_as_arguments() {
+            assert!(parse(&["add", "-a", "GitHub", "-n", "me", "--password", "hunter2"]).is_err());
+            assert!(parse(&["add", "-a", "GitHub", "-n", "me", "--password-stdin"]).is_ok());
+            assert!(parse(&["add", "-a", "GitHub", "-n", "me", "--password-stdin", "--generate"]).is_err());
+        }
+
+        #[test]
+        fn added_entries_need_a_password_source() {
+            assert!(parse(&["add", "-a", "x", "-n", "y"]).is_err());
+            assert!(parse(&["add", "-a", "x", "-n", "y", "--generate"]).is_ok());
+            assert!(parse(&["add", "-a", "x", "-n", "y", "--passphrase"]).is_ok());
+            assert!(parse(&["edit", "x", "-n", "y"]).is_ok());
+        }
+
+        // either would generate an empty password
+        #[test]
+        fn empty_generated_passwords_are_refused() {
+            assert!(parse(&["generate", "--passphrase", "--words", "0"]).is_err());
+            assert!(parse(&["add", "-a", "GitHub", "-n", "me", "-g", "--length", "0", "--lower", "0"]).is_err());
+            assert!(parse(&["generate", "--length", "1", "--lower", "1", "--upper", "0", "--numbers", "0", "--special", "0"]).is_ok());
+        }
+    }
+}
@@ -0,0 +1,414 @@
+/* clipboard module puts passwords on the clipboard so they can be pasted
+   w/o ever being shown, and takes them off again after a while. The
+   clipboard itself is reached through a backend, picked w/the
+   PASSWORD_MANAGER_CLIPBOARD environment variable or else detected. */
+
+pub mod clipboard {
+    use std::fs::{self, OpenOptions};
+    use std::io::{self, Write};
+    use std::path::{Path, PathBuf};
+    use std::process::{Command, Stdio};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+    use crate::error::error::{Error, Result};
+
+    pub const CLIPBOARD_ENV_VAR: &str = "PASSWORD_MANAGER_CLIPBOARD";
+    pub const CLEAR_SECONDS_ENV_VAR: &str = "PASSWORD_MANAGER_CLIPBOARD_SECONDS";
+    const DEFAULT_CLEAR_SECONDS: u64 = 30;
+
+    // somewhere text can be copied to. Backends are shared w/the thread
+    // that clears the clipboard later, so they have to be Send and Sync.
+    pub trait Clipboard: Send + Sync {
+        // shown to the user, such as "wl-copy" or "OSC 52"
+        fn name(&self) -> String;
+        fn set(&self, text: &str) -> io::Result<()>;
+        fn clear(&self) -> io::Result<()>;
+    }
+
+    // copies by piping the text into a clipboard tool. Tools w/o a
+    // command for clearing are cleared by copying nothing.
+    pub struct CommandClipboard {
+        copy: &'static [&'static str],
+        clear: Option<&'static [&'static str]>
+    }
+
+    pub const WL_COPY: CommandClipboard = CommandClipboard { copy: &["wl-copy"], clear: Some(&["wl-copy", "--clear"]) };
+    pub const XCLIP: CommandClipboard = CommandClipboard { copy: &["xclip", "-selection", "clipboard"], clear: None };
+    pub const XSEL: CommandClipboard = CommandClipboard { copy: &["xsel", "--clipboard", "--input"], clear: Some(&["xsel", "--clipboard", "--delete"]) };
+
+    impl CommandClipboard {
+        fn run(command: &[&str], input: &str) -> io::Result<()> {
+            // the tools keep running in the background to serve the clipboard,
+            // so their output is not captured (which would wait for them to exit)
+            let mut child = Command::new(command[0])
+                .args(&command[1..])
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()?;
+
+            // stdin is dropped after writing so the tool sees the end of the text
+            let written = child.stdin.take().expect("stdin was piped").write_all(input.as_bytes());
+            let status = child.wait()?;
+            written?;
+
+            if status.success() {
+                Ok(())
+            } else {
+                Err(io::Error::other(format!("{} failed ({})", command[0], status)))
+            }
+        }
+    }
+
+    impl Clipboard for CommandClipboard {
+        fn name(&self) -> String {
+            self.copy[0].to_string()
+        }
+
+        fn set(&self, text: &str) -> io::Result<()> {
+            CommandClipboard::run(self.copy, text)
+        }
+
+        fn clear(&self) -> io::Result<()> {
+            match self.clear {
+                Some(command) => CommandClipboard::run(command, ""),
+                None => self.set("")
+            }
+        }
+    }
+
+    // asks the terminal to set the clipboard w/an OSC 52 escape sequence, which
+    // also works over ssh. It is written to the terminal directly, so it never
+    // ends up in redirected output.
+    pub struct Osc52Clipboard;
+
+    impl Osc52Clipboard {
+        fn write(data: &str) -> io::Result<()> {
+            let mut tty = OpenOptions::new().write(true).open("/dev/tty")?;
+            write!(tty, "\x1b]52;c;{}\x07", data)?;
+            tty.flush()
+        }
+    }
+
+    impl Clipboard for Osc52Clipboard {
+        fn name(&self) -> String {
+            String::from("OSC 52")
+        }
+
+        fn set(&self, text: &str) -> io::Result<()> {
+            Osc52Clipboard::write(&base64(text.as_bytes()))
+        }
+
+        // terminals clear the clipboard when given data that is not base64
+        fn clear(&self) -> io::Result<()> {
+            Osc52Clipboard::write("!")
+        }
+    }
+
+    // "copies" by writing the text to a file, which is how the tests
+    // can see what would have been on the clipboard
+    pub struct FileClipboard {
+        path: PathBuf
+    }
+
+    impl FileClipboard {
+        pub fn new(path: &Path) -> Self {
+            FileClipboard { path: path.to_path_buf() }
+        }
+    }
+
+    impl Clipboard for FileClipboard {
+        fn name(&self) -> String {
+            format!("file {}", self.path.display())
+        }
+
+        fn set(&self, text: &str) -> io::Result<()> {
+            fs::write(&self.path, text)
+        }
+
+        fn clear(&self) -> io::Result<()> {
+            fs::write(&self.path, "")
+        }
+    }
+
+    // the backend named by PASSWORD_MANAGER_CLIPBOARD: wl-copy, xclip, xsel,
+    // osc52, or file:<path>. W/o it, one is picked to suit the session.
+    pub fn backend_from_env() -> Result<Box<dyn Clipboard>> {
+        match std::env::var(CLIPBOARD_ENV_VAR) {
+            Ok(spec) => parse_backend(&spec),
+            Err(_) => detect_backend().ok_or_else(|| Error::InvalidInput(format!(
+                "no clipboard was found; install wl-copy or xclip, or set {} (to osc52 in a terminal that supports it)",
+                CLIPBOARD_ENV_VAR)))
+        }
+    }
+
+    pub fn parse_backend(spec: &str) -> Result<Box<dyn Clipboard>> {
+        match spec.trim() {
+            "wl-copy" | "wayland" => Ok(Box::new(WL_COPY)),
+            "xclip" | "x11" => Ok(Box::new(XCLIP)),
+            "xsel" => Ok(Box::new(XSEL)),
+            "osc52" => Ok(Box::new(Osc52Clipboard)),
+            spec => match spec.strip_prefix("file:") {
+                Some(path) if !path.is_empty() => Ok(Box::new(FileClipboard::new(Path::new(path)))),
+                _ => Err(Error::InvalidInput(format!(
+                    "{} is not a clipboard; use wl-copy, xclip, xsel, osc52, or file:<path>", spec)))
+            }
+        }
+    }
+
+    // Wayland's tool first, since X11 tools only reach Xwayland's clipboard there
+    fn detect_backend() -> Option<Box<dyn Clipboard>> {
+        let has_env = |name: &str| std::env::var_os(name).is_some_and(|value| !value.is_empty());
+
+        if has_env("WAYLAND_DISPLAY") && on_path("wl-copy") {
+            Some(Box::new(WL_COPY))
+        } else if has_env("DISPLAY") && on_path("xclip") {
+            Some(Box::new(XCLIP))
+        } else if has_env("DISPLAY") && on_path("xsel") {
+            Some(Box::new(XSEL))
+        } else {
+            None
+        }
+    }
+
+    fn on_path(program: &str) -> bool {
+        std::env::var_os("PATH")
+            .is_some_and(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+    }
+
+    // how long copied passwords stay on the clipboard
+    pub fn clear_after() -> Duration {
+        let seconds = std::env::var(CLEAR_SECONDS_ENV_VAR)
+            .ok()
+            .and_then(|seconds| seconds.parse::<u64>().ok())
+            .filter(|seconds| *seconds > 0)
+            .unwrap_or(DEFAULT_CLEAR_SECONDS);
+        Duration::from_secs(seconds)
+    }
+
+    // which copy is on the clipboard. Each copy gets the next number, so the
+    // timer of an older copy can tell that it must leave a newer one alone.
+    struct Pending {
+        copies: u64,
+        on_clipboard: bool
+    }
+
+    // what a timer runs once its time is up
+    pub type Expired = Box<dyn FnOnce() + Send>;
+
+    // calls back once some time has passed, which is when a copy gets cleared
+    pub trait Timer: Send + Sync {
+        fn start(&self, after: Duration, expired: Expired);
+    }
+
+    // waits on a thread of its own for each timer
+    pub struct ThreadTimer;
+
+    impl Timer for ThreadTimer {
+        fn start(&self, after: Duration, expired: Expired) {
+            thread::spawn(move || {
+                thread::sleep(after);
+                expired();
+            });
+        }
+    }
+
+    // copies text w/a backend and clears it again once the timeout runs out
+    pub struct ClipboardCopier {
+        backend: Arc<dyn Clipboard>,
+        clear_after: Duration,
+        timer: Box<dyn Timer>,
+        pending: Arc<Mutex<Pending>>
+    }
+
+    impl ClipboardCopier {
+        pub fn new(backend: Box<dyn Clipboard>, clear_after: Duration) -> Self {
+            ClipboardCopier::with_timer(backend, clear_after, Box::new(ThreadTimer))
+        }
+
+        // same as new, but the copies are cleared when timer says so (such as in tests)
+        pub fn with_timer(backend: Box<dyn Clipboard>, clear_after: Duration, timer: Box<dyn Timer>) -> Self {
+            ClipboardCopier {
+                backend: Arc::from(backend),
+                clear_after,
+                timer,
+                pending: Arc::new(Mutex::new(Pending { copies: 0, on_clipboard: false }))
+            }
+        }
+
+        pub fn from_env() -> Result<Self> {
+            Ok(ClipboardCopier::new(backend_from_env()?, clear_after()))
+        }
+
+        pub fn name(&self) -> String {
+            self.backend.name()
+        }
+
+        pub fn clear_after(&self) -> Duration {
+            self.clear_after
+        }
+
+        pub fn copy(&self, text: &str) -> Result<()> {
+            let copy = {
+                let mut pending = self.pending.lock().expect("clipboard state was poisoned");
+                self.backend.set(text)?;
+                pending.copies += 1;
+                pending.on_clipboard = true;
+                pending.copies
+            };
+
+            let (backend, pending) = (Arc::clone(&self.backend), Arc::clone(&self.pending));
+            self.timer.start(self.clear_after, Box::new(move || {
+                let mut pending = pending.lock().expect("clipboard state was poisoned");
+                if pending.on_clipboard && pending.copies == copy {
+                    pending.on_clipboard = backend.clear().is_err();
+                }
+            }));
+            Ok(())
+        }
+
+        // clears the clipboard now if something copied is still on it,
+        // such as when logging out before the timeout runs out
+        pub fn clear_now(&self) -> Result<()> {
+            let mut pending = self.pending.lock().expect("clipboard state was poisoned");
+            if pending.on_clipboard {
+                self.backend.clear()?;
+                pending.on_clipboard = false;
+            }
+            Ok(())
+        }
+    }
+
+    // standard base64 w/padding, which is all OSC 52 needs
+    fn base64(bytes: &[u8]) -> String {
+        const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+        for chunk in bytes.chunks(3) {
+            let group = (chunk[0] as u32) << 16
+                | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+                | *chunk.get(2).unwrap_or(&0) as u32;
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    encoded.push(ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+                } else {
+                    encoded.push('=');
+                }
+            }
+        }
+        encoded
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn temp_file(name: &str) -> PathBuf {
+            std::env::temp_dir().join(format!("password_manager_clipboard_{}_{}", name, std::process::id()))
+        }
+
+        fn contents(path: &Path) -> String {
+            fs::read_to_string(path).unwrap()
+        }
+
+        // keeps the timers it is given until the test fires them, so nothing waits on the clock
+        #[derive(Clone, Default)]
+        struct ManualTimer {
+            started: Arc<Mutex<Vec<StartedTimer>>>
+        }
+
+        struct StartedTimer {
+            after: Duration,
+            // taken once the timer fires
+            expired: Option<Expired>
+        }
+
+        impl ManualTimer {
+            fn fire(&self, timer: usize) {
+                let expired = self.started.lock().unwrap()[timer].expired.take().expect("the timer already fired");
+                expired();
+            }
+
+            fn durations(&self) -> Vec<Duration> {
+                self.started.lock().unwrap().iter().map(|timer| timer.after).collect()
+            }
+        }
+
+        impl Timer for ManualTimer {
+            fn start(&self, after: Duration, expired: Expired) {
+                self.started.lock().unwrap().push(StartedTimer { after, expired: Some(expired) });
+            }
+        }
+
+        #[test]
+        fn base64_matches_the_standard_alphabet() {
+            let encoded: Vec<String> = ["", "f", "fo", "foo", "foob", "fooba", "foobar"].iter().map(|s| base64(s.as_bytes())).collect();
+            assert_eq!(encoded, ["", "Zg==", "Zm8=", "Zm9v", "Zm9vYg==", "Zm9vYmE=", "Zm9vYmFy"]);
+            assert_eq!(base64(&[0xfb, 0xff]), "+/8=");
+        }
+
+        #[test]
+        fn backends_are_parsed() {
+            assert_eq!(parse_backend("wayland").unwrap().name(), "wl-copy");
+            assert_eq!(parse_backend("xclip").unwrap().name(), "xclip");
+            assert_eq!(parse_backend("osc52").unwrap().name(), "OSC 52");
+            assert_eq!(parse_backend("file:/tmp/clip").unwrap().name(), "file /tmp/clip");
+            assert!(matches!(parse_backend("file:"), Err(Error::InvalidInput(_))));
+            assert!(matches!(parse_backend("pbcopy"), Err(Error::InvalidInput(_))));
+        }
+
+        #[test]
+        fn copies_are_cleared_after_the_timeout() {
+            let path = temp_file("timeout");
+            let timer = ManualTimer::default();
+            let copier = ClipboardCopier::with_timer(Box::new(FileClipboard::new(&path)), Duration::from_secs(30), Box::new(timer.clone()));
+
+            copier.copy("hunter2").unwrap();
+            assert_eq!(contents(&path), "hunter2");
+            assert_eq!(timer.durations(), [Duration::from_secs(30)]);
+            timer.fire(0);
+            assert_eq!(contents(&path), "");
+            let _ = fs::remove_file(path);
+        }
+
+        #[test]
+        fn older_timers_leave_newer_copies_alone() {
+            let path = temp_file("newer");
+            let timer = ManualTimer::default();
+            let copier = ClipboardCopier::with_timer(Box::new(FileClipboard::new(&path)), Duration::from_secs(30), Box::new(timer.clone()));
+
+            copier.copy("first").unwrap();
+            copier.copy("second").unwrap();
+            // the first copy's timer runs out while the second copy is on the clipboard
+            timer.fire(0);
+            assert_eq!(contents(&path), "second");
+            timer.fire(1);
+            assert_eq!(contents(&path), "");
+            let _ = fs::remove_file(path);
+        }
+
+        #[test]
+        fn thread_timers_fire() {
+            let (sender, receiver) = std::sync::mpsc::channel();
+            ThreadTimer.start(Duration::ZERO, Box::new(move || sender.send(()).unwrap()));
+            assert_eq!(receiver.recv_timeout(Duration::from_secs(60)), Ok(()));
+        }
+
+        #[test]
+        fn clear_now_only_clears_pending_copies() {
+            let path = temp_file("clear_now");
+            let copier = ClipboardCopier::new(Box::new(FileClipboard::new(&path)), Duration::from_secs(60));
+
+            copier.copy("hunter2").unwrap();
+            copier.clear_now().unwrap();
+            assert_eq!(contents(&path), "");
+
+            // something the user copied themselves afterwards is not ours to clear
+            fs::write(&path, "theirs").unwrap();
+            copier.clear_now().unwrap();
+            assert_eq!(contents(&path), "theirs");
+            let _ = fs::remove_file(path);
+        }
+    }
+}
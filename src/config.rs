@@ -0,0 +1,99 @@
+/* config module decides which vault (db file) the password manager
+   opens. A vault can be picked w/the --vault flag, the
+   PASSWORD_MANAGER_VAULT environment variable, or the config file,
+   and otherwise defaults to manager.db in the user's data directory. */
+
+pub mod config {
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::Mutex;
+
+    pub const VAULT_ENV_VAR: &str = "PASSWORD_MANAGER_VAULT";
+    const APP_DIR: &str = "password_manager";
+    const DEFAULT_VAULT_FILE: &str = "manager.db";
+
+    // every db connection is opened on this path, so switching
+    // vaults only requires changing it (while nobody is logged in)
+    static ACTIVE_VAULT: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+    // contents of the config file, which holds one "name = path" line per vault.
+    // The line "default = <name or path>" picks the vault opened at startup.
+    pub struct Config {
+        pub default: Option<String>,
+        pub vaults: Vec<(String, PathBuf)>
+    }
+
+    // $XDG_CONFIG_HOME/password_manager/config on Linux
+    pub fn config_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join(APP_DIR).join("config"))
+    }
+
+    // a missing or unreadable config file is treated as an empty one
+    pub fn load_config() -> Config {
+        let contents = config_path().and_then(|path| fs::read_to_string(path).ok()).unwrap_or_default();
+        parse_config(&contents)
+    }
+
+    fn parse_config(contents: &str) -> Config {
+        let mut config = Config { default: None, vaults: Vec::new() };
+
+        for line in contents.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some((name, value)) = line.split_once('=') {
+                let (name, value) = (name.trim(), value.trim());
+                if name == "default" {
+                    config.default = Some(value.to_string());
+                } else {
+                    config.vaults.push((name.to_string(), expand_home(value)));
+                }
+            }
+        }
+        config
+    }
+
+    // lets paths in the config file start w/~ for the home directory
+    fn expand_home(path: &str) -> PathBuf {
+        match (path.strip_prefix("~/"), dirs::home_dir()) {
+            (Some(rest), Some(home)) => home.join(rest),
+            _ => PathBuf::from(path)
+        }
+    }
+
+    // $XDG_DATA_HOME/password_manager/manager.db on Linux
+    pub fn default_vault_path() -> PathBuf {
+        dirs::data_dir()
+            .map(|dir| dir.join(APP_DIR).join(DEFAULT_VAULT_FILE))
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_VAULT_FILE))
+    }
+
+    // a vault can be given either by its name in the config file or by its path
+    pub fn vault_path(config: &Config, name_or_path: &str) -> PathBuf {
+        config.vaults.iter()
+            .find(|(name, _)| name == name_or_path)
+            .map(|(_, path)| path.clone())
+            .unwrap_or_else(|| expand_home(name_or_path))
+    }
+
+    // picks the vault to open at startup. The --vault flag wins, then the
+    // environment variable, then the config file's default, then the data directory.
+    pub fn resolve_vault(vault_arg: Option<&str>) -> PathBuf {
+        let config = load_config();
+        let env_vault = std::env::var(VAULT_ENV_VAR).ok();
+
+        match vault_arg.map(str::to_string).or(env_vault).or(config.default.clone()) {
+            Some(name_or_path) => vault_path(&config, &name_or_path),
+            None => default_vault_path()
+        }
+    }
+
+    pub fn set_active_vault(path: PathBuf) {
+        *ACTIVE_VAULT.lock().expect("vault setting was poisoned") = Some(path);
+    }
+
+    pub fn active_vault() -> PathBuf {
+        let mut active = ACTIVE_VAULT.lock().expect("vault setting was poisoned");
+        active.get_or_insert_with(|| resolve_vault(None)).clone()
+    }
+}
@@ -0,0 +1,251 @@
+/* crypto module deals with turning a client's master password
+   into a vault key and using that key to encrypt and decrypt
+   the account passwords kept in the manager database.      */
+
+pub mod crypto {
+    use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+    use argon2::{Algorithm, Argon2, Params, Version};
+    use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+    use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+    use rand::RngCore;
+    use std::fmt;
+    use subtle::ConstantTimeEq;
+    use zeroize::Zeroize;
+    use crate::secret::secret::Secret;
+
+    pub const KEY_LEN: usize = 32;
+    pub const SALT_LEN: usize = 16;
+    pub const NONCE_LEN: usize = 24; // XChaCha20 uses 192-bit nonces
+
+    // Argon2id costs for master password hashes. Raising any of these makes
+    // every existing hash get rehashed the next time its client logs in.
+    pub const MASTER_M_COST: u32 = 19 * 1024; // memory in KiB
+    pub const MASTER_T_COST: u32 = 2; // iterations
+    pub const MASTER_P_COST: u32 = 1; // parallelism
+
+    // Argon2id costs for deriving vault keys. Each client's costs are stored next to
+    // their salt, so raising these only affects a vault once it is resealed.
+    pub const VAULT_M_COST: u32 = 19 * 1024; // memory in KiB
+    pub const VAULT_T_COST: u32 = 2; // iterations
+    pub const VAULT_P_COST: u32 = 1; // parallelism
+
+    // the Argon2id costs a vault key was derived w/
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct KdfParams {
+        pub m_cost: u32,
+        pub t_cost: u32,
+        pub p_cost: u32
+    }
+
+    impl KdfParams {
+        pub const CURRENT: KdfParams = KdfParams { m_cost: VAULT_M_COST, t_cost: VAULT_T_COST, p_cost: VAULT_P_COST };
+        // what Argon2::default() used (in argon2 0.5) before the costs were stored,
+        // which is how every vault made back then has to be unlocked
+        pub const LEGACY: KdfParams = KdfParams { m_cost: 19 * 1024, t_cost: 2, p_cost: 1 };
+    }
+
+    // outcome of checking a master password against the stored hash
+    #[derive(Debug, PartialEq)]
+    pub enum Verification {
+        Invalid,
+        Valid,
+        ValidNeedsRehash // correct, but stored w/outdated parameters or in plaintext
+    }
+
+    #[derive(Debug)]
+    pub enum CryptoError {
+        KeyDerivation,
+        Encryption,
+        Decryption, // also returned when the ciphertext was tampered with
+        Hashing
+    }
+
+    impl fmt::Display for CryptoError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                CryptoError::KeyDerivation => write!(f, "the vault key could not be derived"),
+                CryptoError::Encryption => write!(f, "the password could not be encrypted"),
+                CryptoError::Decryption => write!(f, "the password could not be decrypted"),
+                CryptoError::Hashing => write!(f, "the master password could not be hashed")
+            }
+        }
+    }
+
+    impl std::error::Error for CryptoError {}
+
+    // symmetric key that every account password of a client is encrypted with.
+    // It only ever lives in memory while the client is logged in.
+    pub struct VaultKey {
+        key: [u8; KEY_LEN]
+    }
+
+    impl VaultKey {
+        // runs the master password through Argon2id (memory-hard, so guessing
+        // master passwords from a stolen db is expensive) using the client's salt
+        // and the costs stored w/it. The algorithm and version are pinned, so a
+        // crate upgrade can never change which key a vault needs.
+        pub fn derive(master_password: &str, salt: &[u8], params: &KdfParams) -> Result<VaultKey, CryptoError> {
+            let params = Params::new(params.m_cost, params.t_cost, params.p_cost, None)
+                .map_err(|_| CryptoError::KeyDerivation)?;
+            let mut key = [0u8; KEY_LEN];
+            Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+                .hash_password_into(master_password.as_bytes(), salt, &mut key)
+                .map_err(|_| CryptoError::KeyDerivation)?;
+
+            Ok(VaultKey { key })
+        }
+
+        // returns the ciphertext and the fresh random nonce it was sealed with.
+        // A new nonce is used for every record, so both must be stored together.
+        // The ciphertext only decrypts w/the same aad, which names where it is
+        // stored, so it cannot be moved to another row or column unnoticed.
+        pub fn encrypt(&self, plaintext: &str, aad: &[u8]) -> Result<(Vec<u8>, Vec<u8>), CryptoError> {
+            let cipher = XChaCha20Poly1305::new((&self.key).into());
+            let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+            let ciphertext = cipher
+                .encrypt(&nonce, Payload { msg: plaintext.as_bytes(), aad })
+                .map_err(|_| CryptoError::Encryption)?;
+
+            Ok((ciphertext, nonce.to_vec()))
+        }
+
+        pub fn decrypt(&self, ciphertext: &[u8], nonce: &[u8], aad: &[u8]) -> Result<Secret, CryptoError> {
+            if nonce.len() != NONCE_LEN {
+                return Err(CryptoError::Decryption);
+            }
+            let cipher = XChaCha20Poly1305::new((&self.key).into());
+            let plaintext = cipher
+                .decrypt(XNonce::from_slice(nonce), Payload { msg: ciphertext, aad })
+                .map_err(|_| CryptoError::Decryption)?;
+
+            String::from_utf8(plaintext).map(Secret::new).map_err(|e| {
+                e.into_bytes().zeroize();
+                CryptoError::Decryption
+            })
+        }
+    }
+
+    // the key is wiped as soon as the vault is locked
+    impl Drop for VaultKey {
+        fn drop(&mut self) {
+            self.key.zeroize();
+        }
+    }
+
+    // random salt that is stored with the client and fed to VaultKey::derive
+    pub fn generate_salt() -> Vec<u8> {
+        let mut salt = vec![0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        salt
+    }
+
+    fn master_params() -> Params {
+        // these values are always valid, so building the params cannot fail
+        Params::new(MASTER_M_COST, MASTER_T_COST, MASTER_P_COST, None).expect("invalid Argon2 parameters")
+    }
+
+    // compares only the costs, since params parsed from a hash also carry its output length
+    fn has_master_costs(params: &Params) -> bool {
+        (params.m_cost(), params.t_cost(), params.p_cost()) == (MASTER_M_COST, MASTER_T_COST, MASTER_P_COST)
+    }
+
+    fn master_hasher() -> Argon2<'static> {
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, master_params())
+    }
+
+    // returns a PHC string ($argon2id$v=19$m=...,t=...,p=...$salt$hash), so the
+    // salt and the parameters used are stored right alongside the hash
+    pub fn hash_master_password(password: &str) -> Result<String, CryptoError> {
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = master_hasher()
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|_| CryptoError::Hashing)?;
+
+        Ok(hash.to_string())
+    }
+
+    // the hash of a password no client has, made w/the current parameters. Checking
+    // a guess against it costs as much as checking a real client's master password.
+    const DUMMY_HASH: &str = "$argon2id$v=19$m=19456,t=2,p=1$IEumXABtNr9vFNVXNJIqLA$m4Q8TCPrYoN/EmQ5zSGscFTzasyPzDdRAgzqTUlArks";
+
+    // does the work of a failed verification for a username that does not exist,
+    // so how long a login takes does not give away which usernames are registered
+    pub fn verify_dummy_password(password: &str) {
+        let _ = verify_master_password(password, DUMMY_HASH);
+    }
+
+    pub fn verify_master_password(password: &str, stored: &str) -> Verification {
+        let hash = match PasswordHash::new(stored) {
+            Ok(hash) => hash,
+            // clients created before hashing was added still have a plaintext password
+            Err(_) => {
+                return if bool::from(password.as_bytes().ct_eq(stored.as_bytes())) {
+                    Verification::ValidNeedsRehash
+                } else {
+                    Verification::Invalid
+                };
+            }
+        };
+
+        // the hash comparison inside verify_password is constant-time
+        if master_hasher().verify_password(password.as_bytes(), &hash).is_err() {
+            return Verification::Invalid;
+        }
+
+        let outdated = hash.algorithm != Algorithm::Argon2id.ident()
+            || hash.version != Some(Version::V0x13.into())
+            || !Params::try_from(&hash).is_ok_and(|params| has_master_costs(&params));
+
+        if outdated {
+            Verification::ValidNeedsRehash
+        } else {
+            Verification::Valid
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        // vaults made before the costs were stored must keep deriving the same key,
+        // which is the one Argon2::default() gave in argon2 0.5
+        #[test]
+        fn legacy_params_derive_the_original_key() {
+            let key = VaultKey::derive("Correct-Horse-42", b"0123456789abcdef", &KdfParams::LEGACY).unwrap();
+            assert_eq!(hex(&key.key), "fbbf62ce9a8ee7cbdc08e57b9a809d5e12c84be70a9a6c3e1a2d5ecfce59ec2a");
+        }
+
+        #[test]
+        fn ciphertexts_only_decrypt_with_their_aad() {
+            let key = VaultKey::derive("Correct-Horse-42", b"0123456789abcdef", &KdfParams::CURRENT).unwrap();
+            let (ciphertext, nonce) = key.encrypt("hunter2", b"row 1").unwrap();
+
+            assert_eq!(key.decrypt(&ciphertext, &nonce, b"row 1").unwrap().expose(), "hunter2");
+            assert!(matches!(key.decrypt(&ciphertext, &nonce, b"row 2"), Err(CryptoError::Decryption)));
+        }
+
+        fn hex(bytes: &[u8]) -> String {
+            bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+        }
+
+        #[test]
+        fn fresh_hashes_do_not_need_rehashing() {
+            let hash = hash_master_password("Correct-Horse-42").unwrap();
+            assert_eq!(verify_master_password("Correct-Horse-42", &hash), Verification::Valid);
+            assert_eq!(verify_master_password("Correct-Horse-43", &hash), Verification::Invalid);
+
+            let weaker = Argon2::new(Algorithm::Argon2id, Version::V0x13, Params::new(8 * 1024, 1, 1, None).unwrap())
+                .hash_password(b"Correct-Horse-42", &SaltString::generate(&mut OsRng)).unwrap().to_string();
+            assert_eq!(verify_master_password("Correct-Horse-42", &weaker), Verification::ValidNeedsRehash);
+        }
+
+        // the dummy hash has to be remade whenever the costs are raised, or
+        // unknown usernames would be checked faster (or slower) than real ones
+        #[test]
+        fn dummy_hash_uses_the_current_parameters() {
+            let hash = PasswordHash::new(DUMMY_HASH).unwrap();
+            assert_eq!(hash.algorithm, Algorithm::Argon2id.ident());
+            assert!(has_master_costs(&Params::try_from(&hash).unwrap()));
+        }
+    }
+}
@@ -0,0 +1,179 @@
+/* error module holds the error type shared by the records, migrations,
+   and cli modules, so callers can tell why something failed (and tell
+   the user what to do about it) instead of only knowing that it did. */
+
+pub mod error {
+    use rusqlite::ffi;
+    use rusqlite::ErrorCode;
+    use std::fmt;
+    use std::io;
+    use crate::crypto::crypto::CryptoError;
+
+    pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+    #[derive(Debug)]
+    pub enum Error {
+        // what was looked for, such as "entry named \"Bank\""
+        NotFound(String),
+        // the username or master password is wrong. Missing usernames also give
+        // this, so nobody can find out which usernames exist by trying to log in.
+        BadCredentials,
+        // the username failed to log in too many times in a row recently
+        LockedOut { retry_after: i64 },
+        // what already exists, such as "user named \"someone\""
+        Duplicate(String),
+        // the db file is damaged or holds data the program cannot read
+        Corruption(String),
+        // the db was written by a newer version of the password manager,
+        // so changing it could lose data this version does not know about
+        UnsupportedSchema { found: u32, supported: u32 },
+        Crypto(CryptoError),
+        Io(io::Error),
+        // any other failure reported by SQLite
+        Database(rusqlite::Error),
+        // a request that cannot be carried out as given, such as impossible generator specs
+        InvalidInput(String)
+    }
+
+    impl Error {
+        // the process exits w/this code when the error ends the program.
+        // 1 is kept for general failures and 2 is used by clap for usage errors.
+        pub fn exit_code(&self) -> i32 {
+            match self {
+                Error::InvalidInput(_) => 1,
+                Error::NotFound(_) => 3,
+                Error::BadCredentials => 4,
+                Error::Duplicate(_) => 5,
+                Error::Corruption(_) | Error::UnsupportedSchema { .. } => 6,
+                Error::Crypto(_) => 7,
+                Error::Io(_) => 8,
+                Error::Database(_) => 9,
+                Error::LockedOut { .. } => 10
+            }
+        }
+
+        // what the user can do about the error
+        pub fn advice(&self) -> &'static str {
+            match self {
+                Error::NotFound(_) => "Check the name or ID and try again.",
+                Error::BadCredentials => "Check the username and master password and try again.",
+                Error::LockedOut { .. } => "Wait until then, and check the username and master password before trying again.",
+                Error::Duplicate(_) => "Choose a different name.",
+                Error::Corruption(_) => "Restore the vault from a backup, or open another vault w/--vault.",
+                Error::UnsupportedSchema { .. } => "Update the password manager to open this vault.",
+                Error::Crypto(CryptoError::Decryption) =>
+                    "The vault may have been changed outside the password manager. Restore it from a backup.",
+                Error::Crypto(_) => "Try again. If it keeps failing, the system may be low on memory.",
+                Error::Io(_) => "Check that the vault's directory exists and that you can write to it.",
+                Error::Database(_) => "Try again. If it keeps failing, the vault may be in use by another program.",
+                Error::InvalidInput(_) => "Change the input and try again."
+            }
+        }
+    }
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                Error::NotFound(what) => write!(f, "no {} was found", what),
+                Error::BadCredentials => write!(f, "the username or master password is incorrect"),
+                Error::LockedOut { retry_after } => write!(f, "too many failed login attempts; try again in {}",
+                    describe_wait(*retry_after)),
+                Error::Duplicate(what) => write!(f, "a {} already exists", what),
+                Error::Corruption(details) => write!(f, "the vault is damaged ({})", details),
+                Error::UnsupportedSchema { found, supported } =>
+                    write!(f, "the vault is at schema version {} but only versions up to {} are supported", found, supported),
+                Error::Crypto(e) => write!(f, "{}", e),
+                Error::Io(e) => write!(f, "the vault could not be accessed ({})", e),
+                Error::Database(e) => write!(f, "a database error occurred ({})", e),
+                Error::InvalidInput(message) => write!(f, "{}", message)
+            }
+        }
+    }
+
+    // rounds up to whole minutes once the wait is longer than a minute
+    pub fn describe_wait(seconds: i64) -> String {
+        match seconds {
+            1 => String::from("1 second"),
+            0..=60 => format!("{} seconds", seconds),
+            _ => format!("{} minutes", (seconds + 59) / 60)
+        }
+    }
+
+    impl std::error::Error for Error {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            match self {
+                Error::Crypto(e) => Some(e),
+                Error::Io(e) => Some(e),
+                Error::Database(e) => Some(e),
+                _ => None
+            }
+        }
+    }
+
+    impl From<CryptoError> for Error {
+        fn from(e: CryptoError) -> Self {
+            Error::Crypto(e)
+        }
+    }
+
+    impl From<io::Error> for Error {
+        fn from(e: io::Error) -> Self {
+            Error::Io(e)
+        }
+    }
+
+    impl From<rusqlite::Error> for Error {
+        fn from(e: rusqlite::Error) -> Self {
+            match e {
+                rusqlite::Error::QueryReturnedNoRows => Error::NotFound(String::from("matching record")),
+                rusqlite::Error::SqliteFailure(ref failure, _) => match failure.code {
+                    ErrorCode::DatabaseCorrupt | ErrorCode::NotADatabase => Error::Corruption(e.to_string()),
+                    ErrorCode::ConstraintViolation if matches!(failure.extended_code,
+                        ffi::SQLITE_CONSTRAINT_UNIQUE | ffi::SQLITE_CONSTRAINT_PRIMARYKEY) =>
+                        Error::Duplicate(String::from("matching record")),
+                    _ => Error::Database(e)
+                },
+                // crypto failures inside row mapping have to travel as a rusqlite error
+                rusqlite::Error::FromSqlConversionFailure(index, kind, source) => match source.downcast::<CryptoError>() {
+                    Ok(crypto) => Error::Crypto(*crypto),
+                    Err(source) => Error::Corruption(rusqlite::Error::FromSqlConversionFailure(index, kind, source).to_string())
+                },
+                rusqlite::Error::InvalidColumnType(..) | rusqlite::Error::IntegralValueOutOfRange(..) =>
+                    Error::Corruption(e.to_string()),
+                _ => Error::Database(e)
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use rusqlite::Connection;
+
+        #[test]
+        fn constraint_violations_are_duplicates() {
+            let conn = Connection::open_in_memory().unwrap();
+            conn.execute_batch("CREATE TABLE t (name TEXT UNIQUE); INSERT INTO t VALUES ('a');").unwrap();
+            let e = Error::from(conn.execute("INSERT INTO t VALUES ('a')", []).unwrap_err());
+
+            assert!(matches!(e, Error::Duplicate(_)), "{:?}", e);
+        }
+
+        #[test]
+        fn missing_rows_are_not_found() {
+            let conn = Connection::open_in_memory().unwrap();
+            let e = Error::from(conn.query_row("SELECT 1 WHERE 0", [], |row| row.get::<_, i64>(0)).unwrap_err());
+
+            assert!(matches!(e, Error::NotFound(_)));
+            assert_eq!(e.exit_code(), 3);
+        }
+
+        #[test]
+        fn crypto_errors_are_unwrapped_from_row_failures() {
+            let wrapped = rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Blob,
+                Box::new(CryptoError::Decryption));
+
+            assert!(matches!(Error::from(wrapped), Error::Crypto(CryptoError::Decryption)));
+        }
+    }
+}
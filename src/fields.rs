@@ -0,0 +1,201 @@
+/* fields module describes the extra information an entry can hold
+   besides its username and password, such as login URLs, recovery
+   codes, security-question answers, and notes. Each custom field
+   has a label, a type, and a value that is checked against its type. */
+
+pub mod fields {
+    use std::fmt;
+    use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+    use crate::error::error::{Error, Result};
+    use crate::secret::secret::Secret;
+
+    // what a field holds, which decides how its value is checked and shown
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    pub enum FieldKind {
+        Text,
+        // masked in listings like passwords are (recovery codes, security answers)
+        Hidden,
+        Url,
+        Email
+    }
+
+    pub const FIELD_KINDS: [FieldKind; 4] = [FieldKind::Text, FieldKind::Hidden, FieldKind::Url, FieldKind::Email];
+
+    impl FieldKind {
+        // the name stored in the fieldType column, which must never change
+        pub fn as_str(&self) -> &'static str {
+            match self {
+                FieldKind::Text => "text",
+                FieldKind::Hidden => "hidden",
+                FieldKind::Url => "url",
+                FieldKind::Email => "email"
+            }
+        }
+
+        // the label used when none is given
+        pub fn default_label(&self) -> &'static str {
+            match self {
+                FieldKind::Text => "Notes",
+                FieldKind::Hidden => "Hidden",
+                FieldKind::Url => "URL",
+                FieldKind::Email => "Email"
+            }
+        }
+
+        // accepts the stored name or its first letter, ignoring case
+        pub fn parse(input: &str) -> Option<FieldKind> {
+            let input = input.trim().to_lowercase();
+            FIELD_KINDS.into_iter().find(|kind| input == kind.as_str() || input == kind.as_str()[..1])
+        }
+    }
+
+    impl fmt::Display for FieldKind {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{}", self.as_str())
+        }
+    }
+
+    impl ToSql for FieldKind {
+        fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+            Ok(ToSqlOutput::from(self.as_str()))
+        }
+    }
+
+    impl FromSql for FieldKind {
+        fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+            let name = value.as_str()?;
+            FIELD_KINDS.into_iter().find(|kind| kind.as_str() == name)
+                .ok_or_else(|| FromSqlError::Other(format!("unknown field type \"{}\"", name).into()))
+        }
+    }
+
+    // a labelled value stored alongside an entry. Every value is encrypted
+    // in the db and wiped from memory, whatever its kind.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct CustomField {
+        pub label: String,
+        pub kind: FieldKind,
+        pub value: Secret
+    }
+
+    impl CustomField {
+        // checks the value against the kind. A blank label gets the kind's default,
+        // and URLs w/o a scheme are assumed to be https.
+        pub fn new(label: &str, kind: FieldKind, value: Secret) -> Result<CustomField> {
+            let label = match label.trim() {
+                "" => kind.default_label(),
+                label => label
+            };
+            let value = match kind {
+                FieldKind::Text | FieldKind::Hidden if value.is_empty() => {
+                    return Err(Error::InvalidInput(format!("{} cannot be empty", label)));
+                }
+                FieldKind::Text | FieldKind::Hidden => value,
+                FieldKind::Url => Secret::new(normalize_url(value.expose())?),
+                FieldKind::Email => {
+                    check_email(value.expose())?;
+                    Secret::new(value.expose().trim().to_string())
+                }
+            };
+
+            Ok(CustomField { label: label.to_string(), kind, value })
+        }
+
+        // what a listing shows for the field, which keeps hidden values masked
+        pub fn display_value(&self, mask: &str) -> String {
+            match self.kind {
+                FieldKind::Hidden => mask.to_string(),
+                _ => self.value.expose().to_string()
+            }
+        }
+    }
+
+    fn normalize_url(url: &str) -> Result<String> {
+        let url = url.trim();
+        let invalid = || Error::InvalidInput(format!("\"{}\" is not a valid URL", url));
+        if url.is_empty() || url.chars().any(char::is_whitespace) {
+            return Err(invalid());
+        }
+
+        let url = match url.split_once("://") {
+            Some((scheme, _)) if scheme.is_empty() || !scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+.-".contains(c)) => {
+                return Err(invalid());
+            }
+            Some(_) => url.to_string(),
+            None => format!("https://{}", url)
+        };
+
+        // the host is everything between the scheme and the first path, query, or fragment
+        let host = url.split_once("://").map(|(_, rest)| rest).unwrap_or_default()
+            .split(['/', '?', '#']).next().unwrap_or_default();
+        match host.is_empty() {
+            true => Err(invalid()),
+            false => Ok(url)
+        }
+    }
+
+    fn check_email(email: &str) -> Result<()> {
+        let email = email.trim();
+        let valid = match email.split_once('@') {
+            Some((local, domain)) => !local.is_empty() && !domain.contains('@')
+                && domain.contains('.') && !domain.starts_with('.') && !domain.ends_with('.')
+                && !email.chars().any(char::is_whitespace),
+            None => false
+        };
+
+        match valid {
+            true => Ok(()),
+            false => Err(Error::InvalidInput(format!("\"{}\" is not a valid email address", email)))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn field(kind: FieldKind, value: &str) -> Result<CustomField> {
+            CustomField::new("", kind, Secret::from(value))
+        }
+
+        #[test]
+        fn kinds_are_parsed_by_name_or_first_letter() {
+            assert_eq!(FieldKind::parse("URL"), Some(FieldKind::Url));
+            assert_eq!(FieldKind::parse(" h "), Some(FieldKind::Hidden));
+            assert_eq!(FieldKind::parse("e"), Some(FieldKind::Email));
+            assert_eq!(FieldKind::parse("note"), None);
+            assert_eq!(FieldKind::parse(""), None);
+        }
+
+        #[test]
+        fn urls_are_checked_and_given_a_scheme() {
+            assert_eq!(field(FieldKind::Url, "github.com/login").unwrap().value.expose(), "https://github.com/login");
+            assert_eq!(field(FieldKind::Url, " http://intranet:8080 ").unwrap().value.expose(), "http://intranet:8080");
+            for url in ["", "not a url", "https://", "://example.com", "ht tp://example.com"] {
+                assert!(matches!(field(FieldKind::Url, url), Err(Error::InvalidInput(_))), "{:?} was accepted", url);
+            }
+        }
+
+        #[test]
+        fn emails_are_checked() {
+            assert!(field(FieldKind::Email, "someone@example.com").is_ok());
+            for email in ["someone", "@example.com", "someone@example", "some one@example.com", "a@b@example.com", "someone@example."] {
+                assert!(matches!(field(FieldKind::Email, email), Err(Error::InvalidInput(_))), "{:?} was accepted", email);
+            }
+        }
+
+        #[test]
+        fn blank_labels_get_a_default() {
+            let field = CustomField::new(" ", FieldKind::Url, Secret::from("example.com")).unwrap();
+            assert_eq!(field.label, "URL");
+            assert_eq!(CustomField::new("Login page", FieldKind::Url, Secret::from("example.com")).unwrap().label, "Login page");
+            assert!(CustomField::new("Notes", FieldKind::Text, Secret::from("")).is_err());
+        }
+
+        #[test]
+        fn hidden_values_are_masked() {
+            let hidden = CustomField::new("Recovery codes", FieldKind::Hidden, Secret::from("1234-5678")).unwrap();
+            assert_eq!(hidden.display_value("****"), "****");
+            assert_eq!(field(FieldKind::Text, "spare key is w/mom").unwrap().display_value("****"), "spare key is w/mom");
+        }
+    }
+}
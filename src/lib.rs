@@ -0,0 +1,21 @@
+/* the password manager's modules live in this library so that both
+   the binary (main.rs) and the integration tests in tests/ can use them. */
+
+// every file wraps its contents in a module of the same name (src/menu.rs holds
+// `pub mod menu { ... }`), so items are reached as crate::menu::menu::Menu
+#![allow(clippy::module_inception)]
+
+pub mod menu;  // Will either look for menu.rs in same directory or, if not
+               // found, a directory named menu w/a mod.rs file in it.
+               // Never attempt to use both options in a single project.
+pub mod records;
+pub mod crypto;
+pub mod strength;
+pub mod cli;
+pub mod terminal;
+pub mod config;
+pub mod migrations;
+pub mod error;
+pub mod clipboard;
+pub mod secret;
+pub mod fields;
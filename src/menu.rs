@@ -3,421 +3,1746 @@
 
 // Rust's module paths do not correspond w/the project's file paths
 pub mod menu {
-    use std::io; // input/output functionality
-    use std::io::Write;
-    use crate::records::records::{AccountInfo, User, Transfer, open_database, Default, Retrieve, generate_password}; // 'crate' begins module search at root of project
-
-    pub fn run_main_menu() {
-        let mut run_program = true;
-        
-        while run_program {
-            // '!' denotes that println! is a macro.
-            print!("Enter 'y' to login or 'n' to close the program: ");
-            let input = get_one_letter_input();
-
-            match input.as_str() {
-                "y" => { 
-                    println!(); 
-                    run_login_menu();
-                },
-                "n" => {
-                    println!();
-                    println!("Goodbye!");
-                    run_program = false;
-                }
-                _ => {
-                    println!();
-                    println!("ERROR: Invalid input detected. Please enter 'y' to login or 'n' to logout.");
-                }
-            } 
+    use std::io::{self, BufRead, Stdout, Write}; // input/output functionality
+    use std::path::PathBuf;
+    use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SendError};
+    use std::thread;
+    use std::time::Duration;
+    use rusqlite::Connection;
+    use zeroize::Zeroize;
+    use crate::strength::strength::{estimate, rating};
+    use crate::terminal::terminal::{hide_echo, invalid_utf8, EchoGuard, read_line_wiped, stdin_is_tty, LINE_CAPACITY};
+    use crate::config::config::{active_vault, load_config, vault_path, config_path};
+    use crate::error::error::{describe_wait, Error};
+    use crate::clipboard::clipboard::ClipboardCopier;
+    use crate::secret::secret::Secret;
+    use crate::fields::fields::{CustomField, FieldKind};
+    use crate::records::records::{self as records, AccountInfo, AuthResult, User, AccountId, Login, Transfer, Register, Settings, Default, Retrieve, generate_password, generate_passphrase, PasswordSpecs, PassphraseSpecs, AMBIGUOUS_CHARS, trash_retention_days, days_until_purge, check_master_password}; // 'crate' begins module search at root of project
+
+    // where the user is in the program. Every screen belongs to one of these,
+    // and the main loop is the only place that moves between them.
+    #[derive(Debug, PartialEq, Clone, Copy)]
+    pub enum State {
+        LoggedOut,
+        Authenticating,
+        LoggedIn,
+        // the session went idle, so the vault is locked until the master password is entered again
+        Locked,
+        Exiting
+    }
+
+    // what happened on the current screen, which decides the next state
+    #[derive(Debug, PartialEq, Clone, Copy)]
+    pub enum Event {
+        Login,
+        Register,
+        SwitchVault,
+        Quit,
+        Invalid,
+        LoginSucceeded,
+        LoginFailed,
+        Logout,
+        IdleTimeout,
+        EndOfInput // standard input was closed, so nothing more can be asked
+    }
+
+    // why a screen stopped before it got an answer. Every prompt hands it back,
+    // so sub-menus can pass it up w/? until the main loop turns it into an event.
+    #[derive(Debug, PartialEq, Clone, Copy)]
+    pub enum Interrupt {
+        EndOfInput,
+        // nothing was entered before the session's auto-lock time ran out
+        IdleTimeout
+    }
+
+    impl From<Interrupt> for Event {
+        fn from(interrupt: Interrupt) -> Self {
+            match interrupt {
+                Interrupt::EndOfInput => Event::EndOfInput,
+                Interrupt::IdleTimeout => Event::IdleTimeout
+            }
+        }
+    }
+
+    // what a prompt gives back: the answer, or why there was none
+    pub type Answer<T> = Result<T, Interrupt>;
+
+    // the choices offered while logged out
+    pub fn parse_main_choice(input: &str) -> Event {
+        match input.trim().to_lowercase().chars().next() {
+            Some('y') => Event::Login,
+            Some('c') => Event::Register,
+            Some('v') => Event::SwitchVault,
+            Some('n') => Event::Quit,
+            _ => Event::Invalid
+        }
+    }
+
+    // listings show this in place of each password, whatever its length
+    pub const PASSWORD_MASK: &str = "********";
+
+    // what can be done w/an entry of a listing, by its number in the listing
+    #[derive(Debug, PartialEq, Clone, Copy)]
+    pub enum ListAction {
+        Reveal(usize),
+        Copy(usize),
+        Back,
+        Invalid
+    }
+
+    // accepts "reveal N" (or "r N"), "copy N" (or "c N"), and blank input or q to go back
+    pub fn parse_list_action(input: &str) -> ListAction {
+        let input = input.trim().to_lowercase();
+        let mut words = input.split_whitespace();
+        let (action, number) = (words.next(), words.next().and_then(|word| word.parse::<usize>().ok()));
+        if words.next().is_some() {
+            return ListAction::Invalid;
+        }
+
+        match (action, number) {
+            (None, _) | (Some("q"), None) => ListAction::Back,
+            (Some("reveal") | Some("r"), Some(number)) if number > 0 => ListAction::Reveal(number),
+            (Some("copy") | Some("c"), Some(number)) if number > 0 => ListAction::Copy(number),
+            _ => ListAction::Invalid
+        }
+    }
+
+    // what can be done to the custom fields of an entry while adding or editing it
+    #[derive(Debug, PartialEq, Clone, Copy)]
+    pub enum FieldAction {
+        Add,
+        Remove(usize),
+        Done,
+        Invalid
+    }
+
+    // accepts a (or "add"), "remove N" (or "r N"), and blank input to finish
+    pub fn parse_field_action(input: &str) -> FieldAction {
+        let input = input.trim().to_lowercase();
+        let mut words = input.split_whitespace();
+        let (action, number) = (words.next(), words.next().map(|word| word.parse::<usize>().ok()));
+        if words.next().is_some() {
+            return FieldAction::Invalid;
+        }
+
+        match (action, number) {
+            (None, _) => FieldAction::Done,
+            (Some("add") | Some("a"), None) => FieldAction::Add,
+            (Some("remove") | Some("r"), Some(Some(number))) if number > 0 => FieldAction::Remove(number),
+            _ => FieldAction::Invalid
         }
     }
-    pub fn run_login_menu() {
-        let mut run_menu = true; 
-        let mut username_input = String::new();
-        let mut password_input = String::new();
-        let mut user = User::default();
-        loop {   
-            println!("Password Manager Login");
-            println!();
-            print!("Enter Username: ");
-            username_input = get_input();
-            
-            if username_input.is_empty() {
-                println!();
-                println!("ERROR: No username entered. Please try again.");
-            }
-            else {
-                println!();
-                print!("Enter Password: ");
-                password_input = get_input();
-
-                if password_input.is_empty() {
-                    println!();
-                    println!("ERROR: No password entered. Please try again.");
-                }
-                else {
-                    match user.lookup_user(&username_input, &password_input) {
-                        // use wildcard, since value has already been set as user_id
-                        // in lookup_user & no longer matters
-                        Ok((Some(client_id),))=> {
-                     
-                            println!();
-                            println!("You are logged in!");
-                    
-                            user.set_client_id(Some(client_id));
-                           
-                            // only stops running when user wants to logout
-                            run_logged_in_menu(&user);
-                            user.set_client_id(None); // Option can either be None or Some()
-                            run_menu = false;
-                            println!();
-                            println!("Logout successful");
 
+    pub fn transition(state: State, event: Event) -> State {
+        match (state, event) {
+            (_, Event::EndOfInput) | (_, Event::Quit) => State::Exiting,
+            (State::LoggedOut, Event::Login) => State::Authenticating,
+            (State::Authenticating, Event::LoginSucceeded) => State::LoggedIn,
+            (State::Authenticating, Event::LoginFailed) => State::LoggedOut,
+            (State::LoggedIn, Event::Logout) => State::LoggedOut,
+            (State::LoggedIn, Event::IdleTimeout) => State::Locked,
+            (State::Locked, Event::LoginSucceeded) => State::LoggedIn,
+            (State::Locked, Event::LoginFailed) => State::LoggedOut,
+            // registering and switching vaults happen while logged out and stay there
+            (state, _) => state
+        }
+    }
+
+    // writes to the menu's output like println! does to stdout
+    macro_rules! outln {
+        ($menu:expr) => {
+            writeln!($menu.output).expect("Failed to write output")
+        };
+        ($menu:expr, $($arg:tt)*) => {
+            writeln!($menu.output, $($arg)*).expect("Failed to write output")
+        };
+    }
+
+    // same as outln!, but w/o the newline (used for prompts)
+    macro_rules! out {
+        ($menu:expr, $($arg:tt)*) => {
+            write!($menu.output, $($arg)*).expect("Failed to write output")
+        };
+    }
+
+    // where the menu gets its answers from, one line at a time
+    pub trait Input {
+        // waits for the next line (w/o its line ending) for up to timeout, or for as long
+        // as it takes if there is none. Gives back Interrupt::IdleTimeout if the time ran
+        // out first, and Interrupt::EndOfInput once the input has been closed.
+        fn next_line(&mut self, timeout: Option<Duration>) -> Answer<String>;
+    }
+
+    // reads lines from any BufRead on a thread of its own, so the menu can stop waiting
+    // for an answer (and lock the session) while the read itself carries on. Lines may be
+    // passwords, so every buffer one was read into is wiped (see read_line_wiped).
+    pub struct LineReader {
+        lines: Receiver<io::Result<String>>
+    }
+
+    impl LineReader {
+        pub fn new<R: BufRead + Send + 'static>(input: R) -> Self {
+            Self::spawn(move || input)
+        }
+
+        // reads stdin w/o a BufReader of its own, so the only copy of a line that cannot
+        // be wiped is the one in std's Stdin buffer (which lives as long as the process)
+        pub fn stdin() -> Self {
+            Self::spawn(|| io::stdin().lock())
+        }
+
+        // the input is opened on the thread, since a locked stdin cannot be sent to it
+        fn spawn<R: BufRead, F: FnOnce() -> R + Send + 'static>(open: F) -> Self {
+            // nothing is sent until the menu asks for it, so the thread reads at most one line ahead
+            let (sender, lines) = mpsc::sync_channel(0);
+            thread::spawn(move || {
+                let mut input = open();
+                loop {
+                    let mut line = Vec::with_capacity(LINE_CAPACITY);
+                    let read = match read_line_wiped(&mut input, &mut line) {
+                        Ok(0) => break, // dropping the sender tells the menu that input ended
+                        Ok(_) => String::from_utf8(line).map_err(|e| {
+                            e.into_bytes().zeroize();
+                            invalid_utf8()
+                        }),
+                        Err(e) => {
+                            line.zeroize();
+                            Err(e)
                         }
-                        Ok((None,)) => {
-                            println!();
-                            println!("The login attempt failed. Please try again.");
-                            run_main_menu();
+                    };
+                    let failed = read.is_err();
+                    // the menu is gone once sending fails, so nobody is left to read for
+                    if let Err(SendError(unread)) = sender.send(read) {
+                        if let Ok(mut line) = unread {
+                            line.zeroize();
+                        }
+                        break;
+                    }
+                    if failed {
+                        break;
+                    }
+                }
+            });
+            LineReader { lines }
+        }
+    }
+
+    impl Input for LineReader {
+        fn next_line(&mut self, timeout: Option<Duration>) -> Answer<String> {
+            let read = match timeout {
+                Some(timeout) => self.lines.recv_timeout(timeout).map_err(|e| match e {
+                    RecvTimeoutError::Timeout => Interrupt::IdleTimeout,
+                    RecvTimeoutError::Disconnected => Interrupt::EndOfInput
+                })?,
+                None => self.lines.recv().map_err(|_| Interrupt::EndOfInput)?
+            };
+            let mut line = read.expect("Failed to read line"); // .expect used for input stream error handling
+
+            // removed in place, since the line may be a password that must not be copied
+            let length = line.trim_end_matches(['\r', '\n']).len();
+            line.truncate(length);
+            Ok(line)
+        }
+    }
+
+    // reads every answer from input and writes every prompt and message to
+    // output, so whole sessions can be run from a script (like in tests)
+    pub struct Menu<I: Input, W: Write> {
+        input: I,
+        output: W,
+        // turns off echo while secrets are typed, so only set it for the stdin terminal
+        hide_secrets: bool,
+        // kept when a secret prompt runs out of time, since the line it was waiting for is
+        // still being typed (and is read by the unlock prompt that always comes next)
+        echo_off: Option<EchoGuard>,
+        // the vault (db file) every connection is opened on
+        vault: PathBuf,
+        // set up the first time a password is copied, unless one was given
+        clipboard: Option<ClipboardCopier>,
+        // who is logged in, which is kept while the session is locked so they can unlock it
+        session: Option<Session>
+    }
+
+    struct Session {
+        username: String,
+        // None if the client chose to never lock their session
+        auto_lock: Option<Duration>,
+        locked: bool
+    }
+
+    // runs the menu on stdin and stdout and returns the exit code for the process
+    pub fn run_main_menu() -> i32 {
+        Menu::stdio().run()
+    }
+
+    impl Menu<LineReader, Stdout> {
+        pub fn stdio() -> Self {
+            let mut menu = Menu::new(LineReader::stdin(), io::stdout(), active_vault());
+            menu.hide_secrets = stdin_is_tty();
+            menu
+        }
+    }
+
+    impl<I: Input, W: Write> Menu<I, W> {
+        pub fn new(input: I, output: W, vault: PathBuf) -> Self {
+            Menu { input, output, hide_secrets: false, echo_off: None, vault, clipboard: None, session: None }
+        }
+
+        // copies passwords w/the given clipboard instead of the one picked from the environment
+        pub fn set_clipboard(&mut self, clipboard: ClipboardCopier) {
+            self.clipboard = Some(clipboard);
+        }
+
+        // gives back the output, such as the transcript of a scripted session
+        pub fn into_output(self) -> W {
+            self.output
+        }
+
+        // runs the whole program until the user quits, and returns the exit code for the process
+        pub fn run(&mut self) -> i32 {
+            outln!(self, "Vault: {}", self.vault.display());
+
+            // nothing can be done w/a vault that cannot be opened
+            let opened = match self.open_vault() {
+                // input ending before the user decides counts as not creating it
+                Err(Error::NotFound(_)) => self.create_vault_menu().unwrap_or_else(|_| Err(records::vault_not_found(&self.vault))),
+                opened => opened.map(drop)
+            };
+            if let Err(e) = opened {
+                self.print_error(&e);
+                return e.exit_code();
+            }
+
+            let mut state = State::LoggedOut;
+            // only set while somebody is logged in (and their session is not locked)
+            let mut user: Option<User> = None;
+
+            while state != State::Exiting {
+                let answer = match state {
+                    State::LoggedOut => self.run_logged_out_menu(),
+                    State::Authenticating => self.run_login_menu(&mut user),
+                    State::LoggedIn => match &user {
+                        Some(user) => self.run_logged_in_menu(user),
+                        None => Ok(Event::Logout) // cannot happen, since logging in is the only way here
+                    },
+                    State::Locked => self.run_unlock_menu(&mut user),
+                    State::Exiting => Ok(Event::Quit)
+                };
+                // a screen that was interrupted (by input ending or the session going idle)
+                // is left wherever it was
+                let event = answer.unwrap_or_else(Event::from);
+                let next = transition(state, event);
+
+                if let Some(session) = self.session.as_mut() {
+                    session.locked = next == State::Locked;
+                }
+
+                // leaving the logged in state always drops the user (and their vault key), however it happens
+                if state == State::LoggedIn && next != State::LoggedIn {
+                    user = None;
+                    // a copied password must not outlive the session it was copied in
+                    if let Some(Err(e)) = self.clipboard.as_ref().map(ClipboardCopier::clear_now) {
+                        outln!(self);
+                        self.print_error(&e);
+                    }
+                }
+                if matches!(state, State::LoggedIn | State::Locked) && matches!(next, State::LoggedOut | State::Exiting) {
+                    self.session = None;
+                    outln!(self);
+                    outln!(self, "Logout successful");
+                }
+                if next == State::Exiting {
+                    outln!(self);
+                    outln!(self, "Goodbye!");
+                }
+                state = next;
+            }
+            0
+        }
+
+        // the first screen, which is shown again after every logout or failed login
+        fn run_logged_out_menu(&mut self) -> Answer<Event> {
+            // prompts are written w/out!, which works like print! but on the menu output
+            out!(self, "Enter 'y' to login, 'c' to create an account, 'v' to switch vaults, or 'n' to close the program: ");
+            let event = parse_main_choice(&self.get_input()?);
+
+            match event {
+                Event::Login => outln!(self),
+                Event::Register => {
+                    outln!(self);
+                    self.run_registration_menu()?;
+                }
+                Event::SwitchVault => {
+                    outln!(self);
+                    self.run_vault_menu()?;
+                }
+                Event::Invalid => {
+                    outln!(self);
+                    outln!(self, "ERROR: Invalid input detected. Please enter 'y' to login, 'c' to create an account, 'v' to switch vaults, or 'n' to close the program.");
+                }
+                _ => {}
+            }
+            Ok(event)
+        }
+
+        // asks for a username and master password, and unlocks the vault if they are correct
+        pub fn run_login_menu(&mut self, user: &mut Option<User>) -> Answer<Event> {
+            outln!(self, "Password Manager Login");
+
+            let username_input = loop {
+                outln!(self);
+                out!(self, "Enter Username: ");
+                let input = self.get_input()?;
+                if input.is_empty() {
+                    outln!(self, "ERROR: No username entered. Please try again.");
+                } else {
+                    break input;
+                }
+            };
+
+            outln!(self);
+            out!(self, "Enter Password: ");
+            let password_input = self.get_secret_input()?;
+            if password_input.is_empty() {
+                outln!(self);
+                outln!(self, "ERROR: No password entered.");
+                return Ok(Event::LoginFailed);
+            }
+
+            let conn = match self.open_vault() {
+                Ok(conn) => conn,
+                Err(e) => {
+                    outln!(self);
+                    self.print_error(&e);
+                    return Ok(Event::LoginFailed);
+                }
+            };
+
+            let event = match User::lookup_user(&conn, &username_input, password_input.expose()) {
+                Ok(AuthResult::Authenticated(client_id)) => {
+                    // the vault key is needed to read or store any passwords
+                    let unlocked = User::unlock_vault(&conn, client_id, password_input.expose())
+                        .and_then(|unlocked| Ok((unlocked.get_auto_lock(&conn)?, unlocked)));
+                    match unlocked {
+                        Ok((auto_lock, unlocked)) => {
+                            *user = Some(unlocked);
+                            self.start_session(username_input, auto_lock);
+                            outln!(self);
+                            outln!(self, "You are logged in!");
+                            Event::LoginSucceeded
                         }
                         Err(e) => {
-                            println!();
-                            println!("An error occurred during the login attempt. Please try again.");
-                            run_main_menu();
+                            outln!(self);
+                            outln!(self, "ERROR: Your vault could not be unlocked.");
+                            self.print_error(&e);
+                            Event::LoginFailed
                         }
                     }
-                    if !run_menu {
+                }
+                Ok(AuthResult::Rejected { retry_after, .. }) => {
+                    outln!(self);
+                    if retry_after > 0 {
+                        outln!(self, "The login attempt failed. Please wait {} before trying again.", describe_wait(retry_after));
+                    } else {
+                        outln!(self, "The login attempt failed. Please try again.");
+                    }
+                    Event::LoginFailed
+                }
+                Ok(AuthResult::LockedOut { retry_after }) => {
+                    outln!(self);
+                    self.print_error(&Error::LockedOut { retry_after });
+                    Event::LoginFailed
+                }
+                Err(e) => {
+                    outln!(self);
+                    self.print_error(&e);
+                    Event::LoginFailed
+                }
+            };
+            Ok(event)
+        }
+        // switches to a vault named in the config file or to any other db file
+        pub fn run_vault_menu(&mut self) -> Answer<()> {
+            let config = load_config();
+
+            outln!(self, "Current vault: {}", self.vault.display());
+            if config.vaults.is_empty() {
+                if let Some(path) = config_path() {
+                    outln!(self, "Vaults can be given names in {} using lines like \"work = ~/work.db\".", path.display());
+                }
+            } else {
+                outln!(self);
+                outln!(self, "Saved vaults:");
+                for (name, path) in config.vaults.iter() {
+                    outln!(self, "  {}: {}", name, path.display());
+                }
+            }
+
+            outln!(self);
+            out!(self, "Enter a vault name or path, or press enter to keep the current vault: ");
+            let input = self.get_input()?;
+
+            if !input.is_empty() {
+                let previous = std::mem::replace(&mut self.vault, vault_path(&config, &input));
+                outln!(self);
+
+                let opened = match self.open_vault() {
+                    Err(Error::NotFound(_)) => self.create_vault_menu()?,
+                    opened => opened.map(drop)
+                };
+
+                // a vault that cannot be opened is never switched to
+                match opened {
+                    Ok(()) => outln!(self, "Switched to {}", self.vault.display()),
+                    Err(e) => {
+                        self.print_error(&e);
+                        self.vault = previous;
+                        outln!(self, "Still using {}", self.vault.display());
+                    }
+                }
+            }
+            outln!(self);
+            Ok(())
+        }
+
+        // asks before creating a vault that does not exist yet, since a new, empty vault
+        // is more often a mistyped name than one the user meant to make
+        fn create_vault_menu(&mut self) -> Answer<Result<(), Error>> {
+            outln!(self, "There is no vault at {}.", self.vault.display());
+            out!(self, "Would you like to create a new vault there? Enter (y/n): ");
+            let create = self.get_one_letter_input()? == "y";
+            outln!(self);
+            if !create {
+                return Ok(Err(records::vault_not_found(&self.vault)));
+            }
+            Ok(records::create_vault(&self.vault).map(|_| outln!(self, "Created {}", self.vault.display())))
+        }
+
+        pub fn run_registration_menu(&mut self) -> Answer<()> {
+            outln!(self, "Create a Password Manager Account");
+
+            loop {
+                outln!(self);
+                out!(self, "Enter a username, or press enter to return: ");
+                let username_input = self.get_input()?;
+
+                if username_input.is_empty() {
+                    break;
+                }
+
+                let conn = match self.open_vault() {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        outln!(self);
+                        outln!(self, "ERROR: The account could not be created.");
+                        self.print_error(&e);
+                        break;
+                    }
+                };
+
+                match User::username_taken(&conn, &username_input) {
+                    Ok(false) => {}
+                    Ok(true) => {
+                        outln!(self);
+                        outln!(self, "ERROR: That username is already taken. Please choose another.");
+                        continue;
+                    }
+                    Err(e) => {
+                        outln!(self);
+                        self.print_error(&e);
+                        continue;
+                    }
+                }
+
+                outln!(self);
+                out!(self, "Enter a master password: ");
+                let password_input = self.get_secret_input()?;
+
+                let problems = check_master_password(password_input.expose(), &username_input);
+                if !problems.is_empty() {
+                    outln!(self);
+                    outln!(self, "ERROR: That master password is too weak.");
+                    for problem in problems.iter() {
+                        outln!(self, "  - {}", problem);
+                    }
+                    continue;
+                }
+
+                outln!(self);
+                out!(self, "Confirm your master password: ");
+                if self.get_secret_input()? != password_input {
+                    outln!(self);
+                    outln!(self, "ERROR: The passwords do not match. Please try again.");
+                    continue;
+                }
+
+                match User::register_user(&conn, &username_input, password_input.expose()) {
+                    Ok(_) => {
+                        outln!(self);
+                        outln!(self, "Your account was created! You can now log in.");
+                        outln!(self);
                         break;
                     }
+                    Err(e) => {
+                        outln!(self);
+                        outln!(self, "ERROR: The account could not be created.");
+                        self.print_error(&e);
+                    }
                 }
             }
+            Ok(())
         }
-    }
-    pub fn run_logged_in_menu(user: &User) {
-        // exit condition for the loop
-        let mut run_menu: bool = true;
-
-        loop {  
-            println!(); 
-            println!("Password Manager Menu:");  
-            println!("1. Add New Entry");
-            println!("2. View All Entries");
-            println!("3. Edit an Entry");
-            println!("4. Delete an Entry");
-            println!("5. Logout");
-            println!();
-            print!("Enter your selection from 1-5: ");
-
-            // will need to allocate data from the heap for a String
-            let mut input = get_input();
-            
 
-            match input.as_str() {
-                "1" => {
-                    add_entry_menu(&user);
-                },
-                "2" => {
-                    let conn = open_database(); 
-                    match conn {
-                        Ok(conn) => {
-                            // used to retrieve just the accounts that belong to the user
-                            let client_id = user.get_id().to_string();
-                            match user.get_accounts(&conn, &client_id) {
+        fn start_session(&mut self, username: String, auto_lock_seconds: u32) {
+            let auto_lock = (auto_lock_seconds > 0).then(|| Duration::from_secs(auto_lock_seconds.into()));
+            self.session = Some(Session { username, auto_lock, locked: false });
+        }
+
+        // how long a prompt may wait for an answer before the session locks. Only
+        // an unlocked session can lock, so there is no limit at any other time.
+        fn idle_timeout(&self) -> Option<Duration> {
+            match &self.session {
+                Some(Session { auto_lock, locked: false, .. }) => *auto_lock,
+                _ => None
+            }
+        }
+
+        // asks for the master password again after the session locked. Anything
+        // other than the right password logs the user out.
+        pub fn run_unlock_menu(&mut self, user: &mut Option<User>) -> Answer<Event> {
+            let (username, auto_lock) = match &self.session {
+                Some(session) => (session.username.clone(), session.auto_lock.unwrap_or_default()),
+                None => return Ok(Event::LoginFailed)
+            };
+
+            outln!(self);
+            outln!(self, "Your session was locked after {} of inactivity.", describe_wait(auto_lock.as_secs() as i64));
+            out!(self, "Enter your master password to continue, or press enter to log out: ");
+            let password_input = self.get_secret_input()?;
+            if password_input.is_empty() {
+                return Ok(Event::LoginFailed);
+            }
+
+            let unlocked = self.open_vault().and_then(|conn| {
+                match User::lookup_user(&conn, &username, password_input.expose())? {
+                    AuthResult::Authenticated(client_id) => User::unlock_vault(&conn, client_id, password_input.expose()).map(Some),
+                    AuthResult::Rejected { .. } => Ok(None),
+                    AuthResult::LockedOut { retry_after } => Err(Error::LockedOut { retry_after })
+                }
+            });
+
+            let event = match unlocked {
+                Ok(Some(unlocked)) => {
+                    *user = Some(unlocked);
+                    outln!(self);
+                    outln!(self, "Your session is unlocked.");
+                    Event::LoginSucceeded
+                }
+                Ok(None) => {
+                    outln!(self);
+                    outln!(self, "ERROR: The master password is incorrect.");
+                    Event::LoginFailed
+                }
+                Err(e) => {
+                    outln!(self);
+                    self.print_error(&e);
+                    Event::LoginFailed
+                }
+            };
+            Ok(event)
+        }
+
+        // runs until the user logs out, or until input ends or the session goes idle
+        // (even in the middle of a sub-menu)
+        pub fn run_logged_in_menu(&mut self, user: &User) -> Answer<Event> {
+            // entries left in the trash past the retention period are removed for good
+            if let Err(e) = self.open_vault().and_then(|conn| AccountInfo::purge_expired(&conn, &trash_retention_days(), &user.get_id())) {
+                outln!(self);
+                outln!(self, "ERROR: Old entries could not be emptied from the trash.");
+                self.print_error(&e);
+            }
+
+            loop {  
+                outln!(self); 
+                outln!(self, "Password Manager Menu:");  
+                outln!(self, "1. Add New Entry");
+                outln!(self, "2. View All Entries");
+                outln!(self, "3. Search Entries");
+                outln!(self, "4. Edit an Entry");
+                outln!(self, "5. Delete an Entry");
+                outln!(self, "6. Restore a Deleted Entry");
+                outln!(self, "7. Auto-Lock Settings");
+                outln!(self, "8. Logout");
+                outln!(self);
+                out!(self, "Enter your selection from 1-8: ");
+
+                // will need to allocate data from the heap for a String
+                let input = self.get_input()?;
+
+                match input.as_str() {
+                    "1" => {
+                        self.add_entry_menu(user)?;
+                    },
+                    "2" => {
+                        let conn = self.open_vault(); 
+                        match conn {
+                            Ok(conn) => {
+                                // only the accounts that belong to the user are retrieved
+                                match user.get_accounts(&conn) {
                                 
-                                Ok(accounts) => {
-                                    let mut num: u8 = 1;
-                                    for account in accounts.iter() {
-                                        println!();
-                                        println!("Account {}: {}", num, account.account);
-                                        println!("Username: {}", account.username);
-                                        println!("Password: {}", account.password);
-                                        num += 1;
+                                    Ok(accounts) => {
+                                        self.print_accounts(&accounts);
+                                        self.list_actions_menu(&accounts)?;
+                                    }
+                                    Err(e) => {
+                                        outln!(self);
+                                        self.print_error(&e);
                                     }
                                 }
-                                Err(e) => {
-                                    println!();
-                                    println!("An error occurred when retrieving accounts from the database.");
-                                   
+                            }
+                            Err(e) => {
+                                outln!(self);
+                                self.print_error(&e);
+                            }
+                        }
+                    },
+                    "3" => {
+                        self.search_menu(user)?;
+                    },
+                    "4" => {
+                        self.edit_entry_menu(user)?;
+                    },
+                    "5" => {
+                        self.delete_entry_menu(user)?;
+                    },
+                    "6" => {
+                        self.trash_menu(user)?;
+                    },
+                    "7" => {
+                        self.auto_lock_menu(user)?;
+                    },
+                    "8" => {  // logout 
+                        return Ok(Event::Logout);
+                    }
+                    _ => { // wildcard input
+                        outln!(self, "ERROR: Invalid input detected. Please enter a number from 1 - 8.");
+                        outln!(self);
+                    }
+                }
+            }
+        }
+
+        // lists the accounts whose name or username matches a search, best matches first
+        pub fn search_menu(&mut self, user: &User) -> Answer<()> {
+            outln!(self);
+            out!(self, "Enter part of an account name or username to search for: ");
+            let query = self.get_input()?;
+
+            match self.open_vault().and_then(|conn| user.find_accounts(&conn, &query)) {
+                Ok(accounts) if accounts.is_empty() => {
+                    outln!(self);
+                    outln!(self, "No entries match \"{}\".", query);
+                }
+                Ok(accounts) => {
+                    outln!(self);
+                    outln!(self, "{} {} \"{}\":", accounts.len(), if accounts.len() == 1 { "entry matches" } else { "entries match" }, query);
+                    self.print_accounts(&accounts);
+                    self.list_actions_menu(&accounts)?;
+                }
+                Err(e) => {
+                    outln!(self);
+                    self.print_error(&e);
+                }
+            }
+            Ok(())
+        }
+
+        // lets the user choose how long their session can be left alone before it locks
+        pub fn auto_lock_menu(&mut self, user: &User) -> Answer<()> {
+            let conn = match self.open_vault() {
+                Ok(conn) => conn,
+                Err(e) => {
+                    outln!(self);
+                    self.print_error(&e);
+                    return Ok(());
+                }
+            };
+
+            outln!(self);
+            match user.get_auto_lock(&conn) {
+                Ok(0) => outln!(self, "Your session never locks on its own."),
+                Ok(seconds) => outln!(self, "Your session locks after {} of inactivity.", describe_wait(seconds.into())),
+                Err(e) => {
+                    self.print_error(&e);
+                    return Ok(());
+                }
+            }
+
+            let minutes = loop {
+                outln!(self);
+                out!(self, "Enter the number of minutes before your session locks (0 to never lock it), or press enter to keep it: ");
+                let input = self.get_input()?;
+                if input.is_empty() {
+                    return Ok(());
+                }
+                match input.parse::<u32>() {
+                    Ok(minutes) if minutes <= u32::MAX / 60 => break minutes,
+                    _ => outln!(self, "ERROR: Please enter a whole number of minutes.")
+                }
+            };
+
+            match user.set_auto_lock(&conn, minutes * 60) {
+                Ok(()) => {
+                    if let Some(session) = self.session.as_mut() {
+                        session.auto_lock = (minutes > 0).then(|| Duration::from_secs(u64::from(minutes) * 60));
+                    }
+                    outln!(self);
+                    outln!(self, "Your auto-lock setting was saved.");
+                }
+                Err(e) => {
+                    outln!(self);
+                    self.print_error(&e);
+                }
+            }
+            Ok(())
+        }
+
+        // lets the user show or copy the passwords of a listing one entry
+        // at a time, until they go back to the main menu (or input ends)
+        fn list_actions_menu(&mut self, accounts: &[AccountInfo]) -> Answer<()> {
+            if accounts.is_empty() {
+                return Ok(());
+            }
+
+            loop {
+                outln!(self);
+                out!(self, "Enter reveal N to show the password of account N, copy N to copy it, or press enter to return to the main menu: ");
+                let input = self.get_input()?;
+
+                match parse_list_action(&input) {
+                    ListAction::Back => return Ok(()),
+                    ListAction::Copy(number) => match accounts.get(number - 1) {
+                        Some(account) => self.copy_password(account),
+                        None => {
+                            outln!(self, "ERROR: There is no account {}. Please enter a number from 1 - {}.", number, accounts.len());
+                        }
+                    },
+                    ListAction::Reveal(number) => match accounts.get(number - 1) {
+                        Some(account) => {
+                            outln!(self);
+                            outln!(self, "Account {}: {}", number, account.account);
+                            outln!(self, "Username: {}", account.username);
+                            outln!(self, "Password: {}", account.password.expose());
+                            for field in account.fields.iter() {
+                                outln!(self, "{}: {}", field.label, field.value.expose());
+                            }
+                        }
+                        None => {
+                            outln!(self, "ERROR: There is no account {}. Please enter a number from 1 - {}.", number, accounts.len());
+                        }
+                    },
+                    ListAction::Invalid => {
+                        outln!(self, "ERROR: Invalid input detected. Please enter reveal or copy followed by an account number.");
+                    }
+                }
+            }
+        }
+
+        pub fn add_entry_menu(&mut self, user: &User) -> Answer<()> {
+            let mut run_options: bool = true;
+
+            while run_options {
+                outln!(self);
+                outln!(self, "Would you like the system to generate a password for you?");
+                out!(self, "Enter (y/n), p for a passphrase, or enter q to return to the main menu: ");
+
+                let input = self.get_one_letter_input()?;
+                let conn = self.open_vault(); 
+
+                match input.as_str() {
+                    "y" | "p" | "n" => {
+                        match conn {
+                            Ok(conn) => {
+                                let mut entry: AccountInfo = match input.as_str() {
+                                    "y" => self.prompt_account_info_generate(Self::get_password_generate)?,
+                                    "p" => self.prompt_account_info_generate(Self::get_passphrase_generate)?,
+                                    _ => self.prompt_account_info_all()?
+                                };
+                                entry.fields = self.prompt_custom_fields(Vec::new())?;
+                                let outcome = AccountInfo::add_account(&conn, entry, &user.get_id(), user.get_vault_key());
+                                match outcome {
+                                    Ok(_) => {
+                                        outln!(self);
+                                        outln!(self, "New account successfully added!");
+                                        outln!(self);
+                                        out!(self, "Would you like to add another account? Enter (y/n): ");
+                                        let input = self.get_one_letter_input()?;
+                                        if input != "y" {
+                                            run_options = false;
+                                        }
+                                    }
+                                    Err(e) => {
+                                        outln!(self);
+                                        outln!(self, "ERROR: The account was not added.");
+                                        self.print_error(&e);
+                                        run_options = false;
+                                    }
+                                }
+                            }
+                            Err(e) => {
+                                outln!(self);
+                                outln!(self, "ERROR: The account could not be added.");
+                                self.print_error(&e);
+                                run_options = false;
+                            } 
+                        }
+                    }
+                    "q" => {
+                        run_options = false;
+                    }
+                    _ => {
+                        outln!(self, "ERROR: Invalid input detected. Please enter 'y', 'n', 'p', or 'q' to return to the main menu.");
+                    }
+                }
+        }
+        Ok(())
+    }
+
+        pub fn edit_entry_menu(&mut self, user: &User) -> Answer<()> {
+            let conn = self.open_vault();
+
+            match conn {
+                Ok(conn) => {
+                    // only the accounts that belong to the user can be chosen
+                    match user.get_accounts(&conn) {
+                        Ok(accounts) => {
+                            if accounts.is_empty() {
+                                outln!(self);
+                                outln!(self, "There are no entries to edit.");
+                            } else {
+                                self.print_account_ids(&accounts);
+                                // None means the user chose to return to the main menu
+                                if let Some((_, account)) = self.select_account(&accounts, "edit")? {
+                                    let entry = self.prompt_account_info_edit(account)?;
+                                    let outcome = AccountInfo::update_account(&conn, entry, &user.get_id(), user.get_vault_key());
+                                    match outcome {
+                                        Ok(_) => {
+                                            outln!(self);
+                                            outln!(self, "Account successfully updated!");
+                                        }
+                                        Err(e) => {
+                                            outln!(self);
+                                            outln!(self, "ERROR: The account was not updated.");
+                                            self.print_error(&e);
+                                        }
+                                    }
                                 }
                             }
                         }
                         Err(e) => {
-                            println!();
-                            println!("An error occurred when connecting to the database.");
-                          
+                            outln!(self);
+                            self.print_error(&e);
                         }
                     }
-                },
-                "3" => {
-                    println!("3!")
-                },
-                "4" => {
-                    println!("4!")
-                },
-                "5" => {  // logout 
-                    run_menu = false; 
                 }
-                _ => { // wildcard input
-                    println!("ERROR: Invalid input detected. Please enter a number from 1 - 5.");
-                    println!();
+                Err(e) => {
+                    outln!(self);
+                    outln!(self, "ERROR: The account could not be edited.");
+                    self.print_error(&e);
                 }
             }
-            if !run_menu {
-                break;
-            }
+            Ok(())
         }
-    }
 
-    pub fn add_entry_menu(user: &User) {
-        let mut run_options: bool = true;
+        pub fn delete_entry_menu(&mut self, user: &User) -> Answer<()> {
+            let conn = self.open_vault();
 
-        while run_options {
-            println!();
-            println!("Would you like the system to generate a password for you?");
-            print!("Enter (y/n), or enter q to return to the main menu: ");
+            match conn {
+                Ok(conn) => {
+                    match user.get_accounts(&conn) {
+                        Ok(accounts) => {
+                            if accounts.is_empty() {
+                                outln!(self);
+                                outln!(self, "There are no entries to delete.");
+                            } else {
+                                self.print_account_ids(&accounts);
+                                // None means the user chose to return to the main menu
+                                if let Some((account_id, account)) = self.select_account(&accounts, "delete")? {
+                                    outln!(self);
+                                    outln!(self, "\"{}\" will be moved to the trash, where it can be restored for {} days.",
+                                        account.account, trash_retention_days());
+                                    out!(self, "Are you sure you want to delete it? Enter (y/n): ");
+
+                                    if self.get_one_letter_input()? == "y" {
+                                        match AccountInfo::delete_account(&conn, &account_id, &user.get_id()) {
+                                            Ok(_) => {
+                                                outln!(self);
+                                                outln!(self, "Account moved to the trash.");
+                                            }
+                                            Err(e) => {
+                                                outln!(self);
+                                                outln!(self, "ERROR: The account was not deleted.");
+                                                self.print_error(&e);
+                                            }
+                                        }
+                                    } else {
+                                        outln!(self);
+                                        outln!(self, "The account was not deleted.");
+                                    }
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            outln!(self);
+                            self.print_error(&e);
+                        }
+                    }
+                }
+                Err(e) => {
+                    outln!(self);
+                    outln!(self, "ERROR: The account could not be deleted.");
+                    self.print_error(&e);
+                }
+            }
+            Ok(())
+        }
 
-            let input = get_one_letter_input();
-            let conn = open_database(); 
+        // lets the user restore or permanently delete accounts in the trash
+        pub fn trash_menu(&mut self, user: &User) -> Answer<()> {
+            let conn = self.open_vault();
 
-            match input.as_str() {
-                "y" | "n" => {
-                    match conn {
-                        Ok(conn) => {
-                            let entry: AccountInfo = if input == "y" {
-                                prompt_account_info_generate()
+            match conn {
+                Ok(conn) => {
+                    let retention_days = trash_retention_days();
+                    match user.get_trash(&conn) {
+                        Ok(trash) => {
+                            if trash.is_empty() {
+                                outln!(self);
+                                outln!(self, "The trash is empty.");
                             } else {
-                                prompt_account_info_all()
-                            };
-                            let outcome = AccountInfo::add_account(&conn, entry, &user.get_id());
-                            match outcome {
-                                Ok(_) => {
-                                    println!();
-                                    println!("New account successfully added!");
-                                    println!();
-                                    print!("Would you like to add another account? Enter (y/n): ");
-                                    let input = get_one_letter_input();
-                                    if input != "y" {
-                                        run_options = false;
+                                outln!(self);
+                                for trashed in trash.iter() {
+                                    // accounts pulled from the db always have an id
+                                    if let Some(id) = trashed.entry.accountId {
+                                        outln!(self, "ID {}: {} ({}) - purged in {} days", id, trashed.entry.account,
+                                            trashed.entry.username, days_until_purge(trashed.deleted_at, retention_days));
                                     }
                                 }
-                                Err(e) => {
-                                    println!();
-                                    println!("ERROR: The account was not added.");
-                                    run_options = false;
+
+                                let entries: Vec<AccountInfo> = trash.into_iter().map(|trashed| trashed.entry).collect();
+                                if let Some((account_id, account)) = self.select_account(&entries, "restore or purge")? {
+
+                                    outln!(self);
+                                    out!(self, "Enter r to restore \"{}\", p to permanently delete it, or q to cancel: ", account.account);
+                                    match self.get_one_letter_input()?.as_str() {
+                                        "r" => {
+                                            match AccountInfo::restore_account(&conn, &account_id, &user.get_id()) {
+                                                Ok(_) => {
+                                                    outln!(self);
+                                                    outln!(self, "Account successfully restored!");
+                                                }
+                                                Err(e) => {
+                                                    outln!(self);
+                                                    outln!(self, "ERROR: The account was not restored.");
+                                                    self.print_error(&e);
+                                                }
+                                            }
+                                        }
+                                        "p" => {
+                                            outln!(self);
+                                            out!(self, "This cannot be undone. Are you sure? Enter (y/n): ");
+                                            if self.get_one_letter_input()? == "y" {
+                                                match AccountInfo::purge_account(&conn, &account_id, &user.get_id()) {
+                                                    Ok(_) => {
+                                                        outln!(self);
+                                                        outln!(self, "Account permanently deleted.");
+                                                    }
+                                                    Err(e) => {
+                                                        outln!(self);
+                                                        outln!(self, "ERROR: The account was not deleted.");
+                                                        self.print_error(&e);
+                                                    }
+                                                }
+                                            }
+                                        }
+                                        _ => {
+                                            outln!(self);
+                                            outln!(self, "No changes were made.");
+                                        }
+                                    }
                                 }
                             }
                         }
                         Err(e) => {
-                            println!();
-                            println!("ERROR: Account could not be added. A failure to connect to the database occurred.");
-                            run_options = false;
-                        } 
+                            outln!(self);
+                            self.print_error(&e);
+                        }
                     }
                 }
-                "q" => {
-                    run_options = false;
+                Err(e) => {
+                    outln!(self);
+                    self.print_error(&e);
                 }
-                _ => {
-                    println!("ERROR: Invalid input detected. Please enter 'y', 'n', or 'q' to return to the main menu.");
+            }
+            Ok(())
+        }
+
+        fn copy_password(&mut self, account: &AccountInfo) {
+            if self.clipboard.is_none() {
+                match ClipboardCopier::from_env() {
+                    Ok(clipboard) => self.clipboard = Some(clipboard),
+                    Err(e) => {
+                        outln!(self);
+                        self.print_error(&e);
+                        return;
+                    }
                 }
             }
-    }
-}
-    // retrieves user input to create a new account entry
-    pub fn prompt_account_info_all() -> AccountInfo {
-        let mut entry = AccountInfo::default();
-        let mut complete: bool = false;
+            let clipboard = self.clipboard.as_ref().expect("the clipboard was just set up");
 
-        loop {
-            
-            let account_name = get_account_name();
-            let username = get_username();
-            let password = get_password();
+            match clipboard.copy(account.password.expose()) {
+                Ok(()) => {
+                    let (name, seconds) = (clipboard.name(), clipboard.clear_after().as_secs());
+                    outln!(self);
+                    outln!(self, "The password for {} was copied w/{}. It will be cleared in {} seconds.", account.account, name, seconds);
+                }
+                Err(e) => {
+                    outln!(self);
+                    self.print_error(&e);
+                }
+            }
+        }
 
-            if !account_name.is_empty() && !username.is_empty() && !password.is_empty() {
-                
-                entry = AccountInfo {      // entry takes ownership of all 4 values
-                    account: account_name,
-                    username,
-                    password,
-                    accountId: None  // id gets assigned after entry is added to db
-                };
-                complete = true;
+        // says what went wrong and what the user can do about it
+        fn print_error(&mut self, e: &Error) {
+            outln!(self, "ERROR: {}.", capitalize(&e.to_string()));
+            outln!(self, "{}", e.advice());
+        }
+
+        // passwords are masked so a listing does not give away the whole vault at once
+        fn print_accounts(&mut self, accounts: &[AccountInfo]) {
+            for (num, account) in accounts.iter().enumerate() {
+                outln!(self);
+                outln!(self, "Account {}: {}", num + 1, account.account);
+                outln!(self, "Username: {}", account.username);
+                outln!(self, "Password: {}", PASSWORD_MASK);
+                for field in account.fields.iter() {
+                    outln!(self, "{}: {}", field.label, field.display_value(PASSWORD_MASK));
+                }
             }
-            if complete {
-                break;
+        }
+
+        // lists accounts alongside the id used to select them
+        fn print_account_ids(&mut self, accounts: &[AccountInfo]) {
+            outln!(self);
+            for account in accounts.iter() {
+                // accounts pulled from the db always have an id
+                if let Some(id) = account.accountId {
+                    outln!(self, "ID {}: {} ({})", id, account.account, account.username);
+                }
             }
         }
-        entry
 
-    }
+        // asks for an account id until one of the listed accounts is chosen, and returns
+        // its id along w/the account. Gives back None if the user enters q instead.
+        fn select_account<'a>(&mut self, accounts: &'a [AccountInfo], action: &str) -> Answer<Option<(AccountId, &'a AccountInfo)>> {
+            loop {
+                outln!(self);
+                out!(self, "Enter the ID of the account to {}, or enter q to return to the main menu: ", action);
+                let input = self.get_input()?;
+
+                if input.to_lowercase() == "q" {
+                    return Ok(None);
+                }
+
+                // ids that do not parse can never match an account
+                let id = input.parse::<AccountId>().ok();
+                match accounts.iter().find(|account| id.is_some() && account.accountId == id) {
+                    Some(account) => return Ok(id.map(|id| (id, account))),
+                    None => {
+                        outln!(self, "ERROR: No account with that ID was found. Please try again.");
+                    }
+                }
+            }
+        }
+
+        // prompts for new values of an existing account. Blank input keeps the current value.
+        pub fn prompt_account_info_edit(&mut self, current: &AccountInfo) -> Answer<AccountInfo> {
+            outln!(self);
+            out!(self, "Enter a new account name, or press enter to keep \"{}\": ", current.account);
+            let account_name = self.get_input()?;
+
+            outln!(self);
+            out!(self, "Enter a new username, or press enter to keep \"{}\": ", current.username);
+            let username = self.get_input()?;
+
+            outln!(self);
+            out!(self, "Would you like to change the password? Enter (y/n): ");
+            let password = if self.get_one_letter_input()? == "y" {
+                outln!(self);
+                out!(self, "Would you like the system to generate a password for you? Enter (y/n), or p for a passphrase: ");
+                match self.get_one_letter_input()?.as_str() {
+                    "y" => self.get_password_generate()?,
+                    "p" => self.get_passphrase_generate()?,
+                    _ => self.get_password(&[&current.account, &current.username])?
+                }
+            } else {
+                current.password.clone()
+            };
+            let fields = self.prompt_custom_fields(current.fields.clone())?;
+
+            Ok(AccountInfo {
+                account: if account_name.is_empty() { current.account.clone() } else { account_name },
+                username: if username.is_empty() { current.username.clone() } else { username },
+                password,
+                accountId: current.accountId,  // id identifies which row gets updated
+                fields
+            })
+        }
+
+        // lets the user add custom fields (URLs, notes, recovery codes, ...) to an
+        // entry or remove them, until they press enter. Returns the fields to keep.
+        fn prompt_custom_fields(&mut self, mut fields: Vec<CustomField>) -> Answer<Vec<CustomField>> {
+            loop {
+                outln!(self);
+                if fields.is_empty() {
+                    out!(self, "Enter a to add a custom field (such as a URL, email, or notes), or press enter to continue: ");
+                } else {
+                    outln!(self, "Custom fields:");
+                    for (num, field) in fields.iter().enumerate() {
+                        outln!(self, "{}. {} ({}): {}", num + 1, field.label, field.kind, field.display_value(PASSWORD_MASK));
+                    }
+                    outln!(self);
+                    out!(self, "Enter a to add another custom field, remove N to remove field N, or press enter to continue: ");
+                }
+
+                match parse_field_action(&self.get_input()?) {
+                    FieldAction::Done => return Ok(fields),
+                    FieldAction::Add => {
+                        let field = self.prompt_custom_field()?;
+                        fields.push(field);
+                    }
+                    FieldAction::Remove(number) if number <= fields.len() => {
+                        fields.remove(number - 1);
+                    }
+                    FieldAction::Remove(number) => {
+                        outln!(self, "ERROR: There is no field {}. Please enter a number from 1 - {}.", number, fields.len());
+                    }
+                    FieldAction::Invalid => {
+                        outln!(self, "ERROR: Invalid input detected. Please enter a, remove followed by a field number, or press enter.");
+                    }
+                }
+            }
+        }
+
+        // asks for the type, label, and value of a new custom field until the value suits the type
+        fn prompt_custom_field(&mut self) -> Answer<CustomField> {
+            let kind = loop {
+                outln!(self);
+                out!(self, "Enter the type of field: text, hidden (masked like a password), url, or email: ");
+                match FieldKind::parse(&self.get_input()?) {
+                    Some(kind) => break kind,
+                    None => outln!(self, "ERROR: Invalid input detected. Please enter text, hidden, url, or email.")
+                }
+            };
+
+            outln!(self);
+            out!(self, "Enter a label for the field, or press enter to call it \"{}\": ", kind.default_label());
+            let label = self.get_input()?;
+
+            loop {
+                outln!(self);
+                out!(self, "Enter the value of {}: ", if label.is_empty() { kind.default_label() } else { &label });
+                // hidden values are typed like passwords are
+                let value = match kind {
+                    FieldKind::Hidden => self.get_secret_input()?,
+                    _ => Secret::new(self.get_input()?)
+                };
+
+                match CustomField::new(&label, kind, value) {
+                    Ok(field) => return Ok(field),
+                    Err(e) => self.print_error(&e)
+                }
+            }
+        }
 
-    // prompts for account name, username, and generates password
-    pub fn prompt_account_info_generate() -> AccountInfo {
-        let mut entry = AccountInfo::default();
-        let mut complete: bool = false;
+        // retrieves user input to create a new account entry
+        pub fn prompt_account_info_all(&mut self) -> Answer<AccountInfo> {
+            let mut entry = AccountInfo::default();
+            let mut complete: bool = false;
 
-        loop {
+            loop {
             
-            let account_name = get_account_name();
-            let username = get_username();
-            let password = get_password_generate();
+                let account_name = self.get_account_name()?;
+                let username = self.get_username()?;
+                let password = self.get_password(&[&account_name, &username])?;
 
-            if !account_name.is_empty() && !username.is_empty() {
+                if !account_name.is_empty() && !username.is_empty() && !password.is_empty() {
+                
+                    entry = AccountInfo {      // entry takes ownership of all 4 values
+                        account: account_name,
+                        username,
+                        password,
+                        accountId: None,  // id gets assigned after entry is added to db
+                        fields: Vec::new()  // asked for separately, once the entry is complete
+                    };
+                    complete = true;
+                }
+                if complete {
+                    break;
+                }
+            }
+            Ok(entry)
+
+        }
+
+        // prompts for account name, username, and generates password
+        // using either the password or the passphrase generator
+        pub fn prompt_account_info_generate(&mut self, generate: fn(&mut Self) -> Answer<Secret>) -> Answer<AccountInfo> {
+            let mut entry = AccountInfo::default();
+            let mut complete: bool = false;
+
+            loop {
+            
+                let account_name = self.get_account_name()?;
+                let username = self.get_username()?;
+                let password = generate(self)?;
+
+                if !account_name.is_empty() && !username.is_empty() {
 
                 
-                entry = AccountInfo {      // entry takes ownership of all 4 values
-                    account: account_name,
-                    username,
-                    password,
-                    accountId: None  // id gets assigned after entry is added to db
-                };
-                complete = true;
+                    entry = AccountInfo {      // entry takes ownership of all 4 values
+                        account: account_name,
+                        username,
+                        password,
+                        accountId: None,  // id gets assigned after entry is added to db
+                        fields: Vec::new()  // asked for separately, once the entry is complete
+                    };
+                    complete = true;
+                }
+                if complete {
+                    break;
+                }
             }
-            if complete {
-                break;
+            Ok(entry)
+
+        }
+        // gives back an Interrupt instead once the input has been closed,
+        // or if the session locks while waiting for an answer
+        fn get_input(&mut self) -> Answer<String> {
+            // always flush the buffer before receiving new input
+            self.output.flush().expect("Failed to flush output");
+
+            let input = self.input.next_line(self.idle_timeout())?;
+            // trim converts input to &str when removing output, 
+            // so to_string() must convert it back to a String
+            Ok(input.trim().to_string())
+        }
+
+        // same as get_input, but the typed characters are not shown on screen.
+        // Only the line ending is removed, since spaces may be part of a password.
+        fn get_secret_input(&mut self) -> Answer<Secret> {
+            self.output.flush().expect("Failed to flush output");
+            // echo stays off for as long as the answer is waited for, which
+            // goes on past this prompt if the session locks first
+            let echo = match self.echo_off.take() {
+                Some(echo) => Some(echo),
+                None => self.hide_secrets.then(hide_echo).transpose().expect("Failed to turn off echo")
+            };
+            let answer = self.input.next_line(self.idle_timeout());
+            if matches!(answer, Err(Interrupt::IdleTimeout)) {
+                self.echo_off = echo;
             }
+            answer.map(Secret::new)
         }
-        entry
 
-    }
-    fn get_input() -> String {
-        // always flush the buffer before receiving new input
-        io::stdout().flush().expect("Failed to flush stdout");
-
-        // mut makes the variable mutable so the input can change
-        let mut input = String::new();
-        // passes in input as a mutable reference
-        io::stdin().read_line(&mut input).expect("Failed to read line"); // .expect used for input stream error handling
-        // trim converts input to &str when removing output, 
-        // so to_string() must convert it back to a String
-        input.trim().to_string()
-    }
+        // input requires some manipulation to return single letters (i.e. 'y' or 'n')
+        fn get_one_letter_input(&mut self) -> Answer<String> {
+            // get_input has already removed the whitespace before the first character
+            let input = self.get_input()?;
+            Ok(input.to_lowercase().chars().next().unwrap_or('\0').to_string()) // no semicolon to return value
+        }
 
-    // input requires some manipulation to return single letters (i.e. 'y' or 'n')
-    fn get_one_letter_input() -> String {
+        fn open_vault(&self) -> Result<Connection, Error> {
+            records::open_vault(&self.vault)
+        }
 
-        io::stdout().flush().expect("Failed to flush stdout");
-        // same as get_input
-        let mut input = String::new();
+        fn get_account_name(&mut self) -> Answer<String> {
 
-        // must remove whitespace before retrieving first character from input
-        io::stdin().read_line(&mut input).expect("Failed to read line");
-        input.trim().to_string();
-        input.to_lowercase().chars().nth(0).unwrap_or('\0').to_string() // no semicolon to return value
-    }
+            self.output.flush().expect("Failed to flush output");
+            let mut account_name_empty: bool = true;
 
-    fn get_account_name() -> String {
+            // loses ownership too soon if defined inside loop
+            let mut account_name;
 
-        io::stdout().flush().expect("Failed to flush stdout");
-        let mut account_name_empty: bool = true;
+            loop {
+                outln!(self); 
+                out!(self, "Enter the name of your account: ");
+                account_name = self.get_input()?;
+                if account_name.is_empty() {
+                    outln!(self, "ERROR: No account name entered. Please try again.");
+                    outln!(self);
+                } else {
+                    account_name_empty = false;
+                }
 
-        // loses ownership too soon if defined inside loop
-        let mut account_name = String::new();
+                if !account_name_empty {
+                    break;
+                }
+            }
+            Ok(account_name) // return
+        }
 
-        loop {
-            println!(); 
-            print!("Enter the name of your account: ");
-            account_name = get_input();
-            if account_name.is_empty() {
-                println!("ERROR: No account name entered. Please try again.");
-                println!();
-            } else {
-                account_name_empty = false;
+        fn get_username(&mut self) -> Answer<String> {
+
+            self.output.flush().expect("Failed to flush output");
+            let mut username_empty: bool = true;
+
+            // loses ownership too soon if defined inside loop
+            let mut username;
+
+            loop {
+                outln!(self); 
+                out!(self, "Enter your account username: ");
+                username = self.get_input()?;
+                if username.is_empty() {
+                    outln!(self, "ERROR: No username entered. Please try again.");
+                    outln!(self);
+                } else {
+                    username_empty = false;
+                }
+
+                if !username_empty {
+                    break;
+                }
+            }
+            Ok(username)
+        }
+
+        // user_inputs are the account name and username, which make
+        // a password easier to guess if it contains them
+        fn get_password(&mut self, user_inputs: &[&str]) -> Answer<Secret> {
+
+            self.output.flush().expect("Failed to flush output");
+            let mut password_empty: bool = true;
+            let mut password;
+
+            loop {
+                outln!(self); 
+                out!(self, "Enter your account password: ");
+                password = self.get_secret_input()?;
+                if password.is_empty() {
+                    outln!(self, "ERROR: No password entered. Please try again.");
+                    outln!(self);
+                    continue;
+                }
+
+                self.print_strength(password.expose(), user_inputs);
+                outln!(self);
+                out!(self, "Enter 'y' to use this password, 'n' to enter a different one, or 'g' to generate one instead: ");
+                match self.get_one_letter_input()?.as_str() {
+                    "y" => password_empty = false,
+                    "g" => {
+                        password = self.get_password_generate()?;
+                        password_empty = false;
+                    }
+                    _ => {}
+                }
+
+                if !password_empty {
+                    break;
+                }
             }
 
-            if !account_name_empty {
-                break;
+            Ok(password)
+        }
+
+        // shows the estimated strength of a password along w/how to improve it
+        fn print_strength(&mut self, password: &str, user_inputs: &[&str]) {
+            let result = estimate(password, user_inputs);
+
+            outln!(self);
+            outln!(self, "Password strength: {} ({}/4), about 10^{:.0} guesses to crack", rating(result.score), result.score, result.guesses.log10());
+            if let Some(warning) = result.warning {
+                outln!(self, "Warning: {}", warning);
+            }
+            for suggestion in result.suggestions.iter() {
+                outln!(self, "  - {}", suggestion);
             }
         }
-        account_name // return
-    }
 
-    fn get_username() -> String {
+        fn get_password_generate(&mut self) -> Answer<Secret> {
 
-        io::stdout().flush().expect("Failed to flush stdout");
-        let mut username_empty: bool = true;
+            self.output.flush().expect("Failed to flush output");
+            let specs = self.get_password_specs()?;
+            let mut password_empty: bool = true;
+            let mut password = Secret::default();
 
-        // loses ownership too soon if defined inside loop
-        let mut username = String::new();
+            loop {
+                outln!(self); 
+                out!(self, "Enter the length of your password: ");
+                let length_string = self.get_input()?;
 
-        loop {
-            println!(); 
-            print!("Enter your account username: ");
-            username = get_input();
-            if username.is_empty() {
-                println!("ERROR: No username entered. Please try again.");
-                println!();
-            } else {
-                username_empty = false;
+                // parse string as 8-bit unsigned int
+                match length_string.parse::<u8>() {
+                    Ok(length) => {
+                        match generate_password(length, &specs) {
+                            Ok(generated) => password = generated,
+                            Err(e) => outln!(self, "ERROR: Password generation failed because {}.", e)
+                        }
+                    }
+                    Err(_) => {
+                        outln!(self, "ERROR: The new password length could not be found.");
+                    }
+                }
+            
+                if password.is_empty() {
+                    outln!(self, "Please try again.");
+                    outln!(self);
+                } else {
+                    password_empty = false;
+                }
+
+                if !password_empty {
+                    break;
+                }
+            }
+
+            Ok(password)
+        }
+
+        fn get_passphrase_generate(&mut self) -> Answer<Secret> {
+            let mut specs = PassphraseSpecs::default();
+
+            loop {
+                outln!(self);
+                out!(self, "Enter the number of words in your passphrase, or press enter for {}: ", specs.words);
+                let input = self.get_input()?;
+
+                if input.is_empty() {
+                    break;
+                }
+                match input.parse::<u8>() {
+                    Ok(words) if words > 0 => {
+                        specs.words = words;
+                        break;
+                    }
+                    _ => outln!(self, "ERROR: Please enter a whole number from 1 to 255.")
+                }
             }
 
-            if !username_empty {
-                break;
+            outln!(self);
+            out!(self, "Enter the separator between words, or press enter for \"{}\": ", specs.separator);
+            let separator = self.get_input()?;
+            if !separator.is_empty() {
+                specs.separator = separator;
             }
+
+            outln!(self);
+            out!(self, "Capitalize each word? Enter (y/n): ");
+            specs.capitalize = self.get_one_letter_input()? == "y";
+
+            outln!(self);
+            out!(self, "Add a random number to one of the words? Enter (y/n): ");
+            specs.add_digit = self.get_one_letter_input()? == "y";
+
+            let (passphrase, entropy) = generate_passphrase(&specs);
+            outln!(self);
+            outln!(self, "Your passphrase has about {:.0} bits of entropy.", entropy);
+
+            Ok(passphrase)
         }
-        username
-    }
 
-    fn get_password() -> String {
+        // lets the user adjust the default requirements for generated passwords
+        fn get_password_specs(&mut self) -> Answer<PasswordSpecs> {
+            let mut specs = PasswordSpecs::default();
 
-        io::stdout().flush().expect("Failed to flush stdout");
-        let mut password_empty: bool = true;
-        let mut password = String::new();
+            outln!(self);
+            outln!(self, "Generated passwords contain at least {} lowercase letters, {} uppercase letters, {} numbers, and {} special characters.",
+                specs.lower_letters, specs.upper_letters, specs.numbers, specs.special_chars);
+            out!(self, "Would you like to change these requirements? Enter (y/n): ");
 
-        loop {
-            println!(); 
-            print!("Enter your account password: ");
-            password = get_input();
-            if password.is_empty() {
-                println!("ERROR: No password entered. Please try again.");
-                println!();
-            } else {
-                password_empty = false;
+            if self.get_one_letter_input()? == "y" {
+                specs.lower_letters = self.get_minimum("lowercase letters", specs.lower_letters)?;
+                specs.upper_letters = self.get_minimum("uppercase letters", specs.upper_letters)?;
+                specs.numbers = self.get_minimum("numbers", specs.numbers)?;
+                specs.special_chars = self.get_minimum("special characters", specs.special_chars)?;
+
+                outln!(self);
+                out!(self, "Enter any characters that should never be used, or press enter to skip: ");
+                specs.excluded_chars = self.get_input()?;
+
+                outln!(self);
+                out!(self, "Avoid characters that are easy to mix up ({})? Enter (y/n): ", AMBIGUOUS_CHARS);
+                specs.avoid_ambiguous = self.get_one_letter_input()? == "y";
             }
+            Ok(specs)
+        }
 
-            if !password_empty {
-                break;
+        // blank input keeps the current minimum
+        fn get_minimum(&mut self, class: &str, current: u8) -> Answer<u8> {
+            loop {
+                outln!(self);
+                out!(self, "Enter the minimum number of {}, or press enter to keep {}: ", class, current);
+                let input = self.get_input()?;
+
+                if input.is_empty() {
+                    return Ok(current);
+                }
+                match input.parse::<u8>() {
+                    Ok(minimum) => return Ok(minimum),
+                    Err(_) => outln!(self, "ERROR: Please enter a whole number from 0 to 255.")
+                }
             }
         }
+    }
 
-        password
+    fn capitalize(message: &str) -> String {
+        let mut chars = message.chars();
+        match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => String::new()
+        }
     }
 
-    fn get_password_generate() -> String {
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::io::{BufReader, Cursor};
 
-        io::stdout().flush().expect("Failed to flush stdout");
-        let mut password_empty: bool = true;
-        let mut password = String::new();
+        // feeds the events in order through the state machine and returns every state visited
+        fn run_script(events: &[Event]) -> Vec<State> {
+            let mut state = State::LoggedOut;
+            let mut visited = vec![state];
+            for event in events {
+                state = transition(state, *event);
+                visited.push(state);
+            }
+            visited
+        }
 
-        loop {
-            println!(); 
-            print!("Enter the length of your password: ");
-            let length_string = get_input();
+        // input that only hands over what the test sends it, and blocks until then
+        struct ChannelInput(mpsc::Receiver<Vec<u8>>);
 
-            // parse string as 8-bit unsigned int
-            match length_string.parse::<u8>() {
-                Ok(length) => {
-                    password = generate_password(length);
-                }
-                Err(e) => {
-                    println!("ERROR: The new password length could not be found.");
+        impl io::Read for ChannelInput {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                match self.0.recv() {
+                    Ok(bytes) => {
+                        buf[..bytes.len()].copy_from_slice(&bytes);
+                        Ok(bytes.len())
+                    }
+                    Err(_) => Ok(0)
                 }
             }
-            
-            if password.is_empty() {
-                println!("ERROR: Password generation failed.");
-                println!();
-            } else {
-                password_empty = false;
+        }
+
+        #[test]
+        fn line_reader_stops_waiting_when_the_time_runs_out() {
+            let (sender, receiver) = mpsc::channel();
+            let mut reader = LineReader::new(BufReader::new(ChannelInput(receiver)));
+
+            assert_eq!(reader.next_line(Some(Duration::from_millis(10))), Err(Interrupt::IdleTimeout));
+            // the line that was being read when the time ran out is handed over next
+            sender.send(b"hunter2 \r\n".to_vec()).unwrap();
+            assert_eq!(reader.next_line(None), Ok(String::from("hunter2 ")));
+            drop(sender);
+            assert_eq!(reader.next_line(Some(Duration::from_secs(60))), Err(Interrupt::EndOfInput));
+        }
+
+        // lines longer than the buffer they start in are grown into a new one, and the
+        // last line is read even w/o a line ending
+        #[test]
+        fn line_reader_reads_long_and_unterminated_lines() {
+            let long = "x".repeat(LINE_CAPACITY * 3);
+            let mut reader = LineReader::new(Cursor::new(format!("{}\nlast", long).into_bytes()));
+
+            assert_eq!(reader.next_line(None), Ok(long));
+            assert_eq!(reader.next_line(None), Ok(String::from("last")));
+            assert_eq!(reader.next_line(None), Err(Interrupt::EndOfInput));
+        }
+
+        #[test]
+        fn field_actions_are_parsed() {
+            assert_eq!(parse_field_action(""), FieldAction::Done);
+            assert_eq!(parse_field_action(" A "), FieldAction::Add);
+            assert_eq!(parse_field_action("remove 2"), FieldAction::Remove(2));
+            assert_eq!(parse_field_action("r 1"), FieldAction::Remove(1));
+            for input in ["r", "r 0", "a 1", "remove x", "delete 1", "r 1 2"] {
+                assert_eq!(parse_field_action(input), FieldAction::Invalid, "{}", input);
             }
+        }
 
-            if !password_empty {
-                break;
+        #[test]
+        fn list_actions_are_parsed() {
+            assert_eq!(parse_list_action("reveal 2"), ListAction::Reveal(2));
+            assert_eq!(parse_list_action(" R 10 "), ListAction::Reveal(10));
+            assert_eq!(parse_list_action(""), ListAction::Back);
+            assert_eq!(parse_list_action("q"), ListAction::Back);
+            assert_eq!(parse_list_action("copy 3"), ListAction::Copy(3));
+            assert_eq!(parse_list_action("c 1"), ListAction::Copy(1));
+            for input in ["reveal", "reveal 0", "reveal two", "reveal 1 2", "copy", "copy -1", "2"] {
+                assert_eq!(parse_list_action(input), ListAction::Invalid, "{}", input);
             }
         }
 
-        password
-    }
+        #[test]
+        fn main_choices_are_parsed() {
+            let choices: Vec<Event> = ["y", "C", " v ", "no", "x", ""].iter().map(|input| parse_main_choice(input)).collect();
+            assert_eq!(choices, [Event::Login, Event::Register, Event::SwitchVault, Event::Quit, Event::Invalid, Event::Invalid]);
+        }
 
-}
\ No newline at end of file
+        #[test]
+        fn failed_login_returns_to_logged_out() {
+            let states = run_script(&[Event::Login, Event::LoginFailed, Event::Login, Event::LoginFailed]);
+            assert_eq!(states, [State::LoggedOut, State::Authenticating, State::LoggedOut, State::Authenticating, State::LoggedOut]);
+        }
+
+        // quitting must work the same however many logins failed before it
+        #[test]
+        fn quit_exits_after_failed_logins() {
+            let mut events = [Event::Login, Event::LoginFailed].repeat(5);
+            events.push(parse_main_choice("n"));
+            assert_eq!(run_script(&events).last(), Some(&State::Exiting));
+        }
+
+        #[test]
+        fn logout_returns_to_logged_out() {
+            let states = run_script(&[Event::Login, Event::LoginSucceeded, Event::Logout, Event::Quit]);
+            assert_eq!(states, [State::LoggedOut, State::Authenticating, State::LoggedIn, State::LoggedOut, State::Exiting]);
+        }
+
+        #[test]
+        fn logged_out_actions_stay_logged_out() {
+            for event in [Event::Register, Event::SwitchVault, Event::Invalid] {
+                assert_eq!(transition(State::LoggedOut, event), State::LoggedOut);
+            }
+        }
+
+        #[test]
+        fn end_of_input_exits_from_every_state() {
+            for state in [State::LoggedOut, State::Authenticating, State::LoggedIn, State::Locked] {
+                assert_eq!(transition(state, Event::EndOfInput), State::Exiting);
+            }
+        }
+
+        #[test]
+        fn idle_sessions_lock_until_unlocked() {
+            let states = run_script(&[Event::Login, Event::LoginSucceeded, Event::IdleTimeout, Event::LoginSucceeded, Event::Logout]);
+            assert_eq!(states, [State::LoggedOut, State::Authenticating, State::LoggedIn, State::Locked, State::LoggedIn, State::LoggedOut]);
+        }
+
+        #[test]
+        fn failing_to_unlock_logs_out() {
+            assert_eq!(transition(State::Locked, Event::LoginFailed), State::LoggedOut);
+            assert_eq!(transition(State::Locked, Event::Invalid), State::Locked);
+        }
+
+        // only a successful login can reach the logged in state
+        #[test]
+        fn logged_in_is_only_reached_by_logging_in() {
+            for event in [Event::Login, Event::Register, Event::SwitchVault, Event::Invalid, Event::LoginSucceeded, Event::Logout] {
+                assert_ne!(transition(State::LoggedOut, event), State::LoggedIn);
+            }
+        }
+    }
+}
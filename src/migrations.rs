@@ -0,0 +1,240 @@
+/* migrations module keeps the schema of the manager database up to
+   date. The version of a db file is kept in its user_version pragma,
+   and every migration newer than that is applied in order when the
+   db is opened, so older files are upgraded w/o losing any data.  */
+
+pub mod migrations {
+    use rusqlite::{Connection, Result};
+    use crate::error::error::Error;
+
+    // a change to the schema. Migrations are never edited once released;
+    // a new one is appended to MIGRATIONS instead.
+    pub struct Migration {
+        pub version: u32,
+        pub apply: fn(&Connection) -> Result<()>
+    }
+
+    // must stay sorted by version, w/no gaps
+    pub const MIGRATIONS: &[Migration] = &[
+        Migration { version: 1, apply: create_base_tables },
+        Migration { version: 2, apply: create_trash_table },
+        Migration { version: 3, apply: add_encryption_columns },
+        Migration { version: 4, apply: create_failed_logins_table },
+        Migration { version: 5, apply: add_auto_lock_column },
+        Migration { version: 6, apply: create_account_fields_table },
+        Migration { version: 7, apply: add_vault_format_columns }
+    ];
+
+    pub fn latest_version() -> u32 {
+        MIGRATIONS.last().map(|migration| migration.version).unwrap_or_default()
+    }
+
+    pub fn schema_version(conn: &Connection) -> Result<u32> {
+        conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+    }
+
+    // each migration runs in its own transaction together w/the version bump,
+    // so a failure leaves the db at the last version that fully applied
+    pub fn migrate(conn: &Connection) -> Result<(), Error> {
+        let current = schema_version(conn)?;
+        let latest = latest_version();
+        if current > latest {
+            return Err(Error::UnsupportedSchema { found: current, supported: latest });
+        }
+
+        for migration in MIGRATIONS.iter().filter(|migration| migration.version > current) {
+            let tx = conn.unchecked_transaction()?;
+            (migration.apply)(&tx)?;
+            tx.pragma_update(None, "user_version", migration.version)?;
+            tx.commit()?;
+        }
+        Ok(())
+    }
+
+    // db files made before migrations existed are all at version 0, whatever
+    // their schema looks like, so the first few migrations have to tolerate
+    // tables and columns that are already there
+
+    // the clients and accounts tables
+    fn create_base_tables(conn: &Connection) -> Result<()> {
+        conn.execute_batch(r#"
+        CREATE TABLE IF NOT EXISTS clients (
+            clientId INTEGER PRIMARY KEY AUTOINCREMENT,
+            username TEXT NOT NULL UNIQUE,
+            password TEXT NOT NULL);
+
+        -- the clientId foreign key in accounts links the data so all
+        -- accounts for a particular client can be easily found
+        CREATE TABLE IF NOT EXISTS accounts (
+            accountId INTEGER PRIMARY KEY AUTOINCREMENT,
+            clientId INTEGER REFERENCES clients(clientId) ON DELETE CASCADE ON UPDATE CASCADE,
+            accountName TEXT NOT NULL,
+            accountUsername TEXT NOT NULL,
+            accountPassword TEXT NOT NULL);
+        "#)
+    }
+
+    // holds deleted accounts until they are restored or purged
+    fn create_trash_table(conn: &Connection) -> Result<()> {
+        conn.execute_batch(r#"
+        CREATE TABLE IF NOT EXISTS trash (
+            accountId INTEGER PRIMARY KEY,
+            clientId INTEGER REFERENCES clients(clientId) ON DELETE CASCADE ON UPDATE CASCADE,
+            accountName TEXT NOT NULL,
+            accountUsername TEXT NOT NULL,
+            accountPassword TEXT NOT NULL,
+            deletedAt INTEGER NOT NULL DEFAULT (strftime('%s', 'now')));
+        "#)
+    }
+
+    // the salt each client's vault key is derived with, and the nonce each
+    // password was encrypted with (NULL while the password is still plaintext)
+    fn add_encryption_columns(conn: &Connection) -> Result<()> {
+        add_column_if_missing(conn, "clients", "kdfSalt", "BLOB")?;
+        add_column_if_missing(conn, "accounts", "passwordNonce", "BLOB")?;
+        add_column_if_missing(conn, "trash", "passwordNonce", "BLOB")
+    }
+
+    // failed login attempts in a row for each username tried, so repeated
+    // guessing is slowed down even across separate runs of the program
+    fn create_failed_logins_table(conn: &Connection) -> Result<()> {
+        conn.execute_batch(r#"
+        CREATE TABLE failedLogins (
+            username TEXT PRIMARY KEY,
+            failures INTEGER NOT NULL,
+            lastFailure INTEGER NOT NULL);
+        "#)
+    }
+
+    // seconds of inactivity before each client's session locks.
+    // NULL uses the default, and 0 means the session never locks.
+    fn add_auto_lock_column(conn: &Connection) -> Result<()> {
+        add_column_if_missing(conn, "clients", "autoLockSeconds", "INTEGER")
+    }
+
+    // custom fields of each entry (URLs, notes, recovery codes, ...), in the order
+    // they were added. Rows are keyed by accountId alone, so they stay put while
+    // their entry moves to the trash and back, and values are encrypted like passwords.
+    fn create_account_fields_table(conn: &Connection) -> Result<()> {
+        conn.execute_batch(r#"
+        CREATE TABLE accountFields (
+            fieldId INTEGER PRIMARY KEY AUTOINCREMENT,
+            accountId INTEGER NOT NULL,
+            fieldLabel TEXT NOT NULL,
+            fieldType TEXT NOT NULL,
+            fieldValue BLOB NOT NULL,
+            valueNonce BLOB NOT NULL);
+        CREATE INDEX accountFieldsByAccount ON accountFields (accountId);
+        "#)
+    }
+
+    // the Argon2id costs each client's vault key is derived w/, and which format
+    // their ciphertexts are in (0 is not bound to any row, 1 is bound w/associated
+    // data). Clients that already have a salt had their key derived w/the argon2
+    // crate's defaults at the time, which are pinned here so they can always unlock.
+    fn add_vault_format_columns(conn: &Connection) -> Result<()> {
+        add_column_if_missing(conn, "clients", "kdfMemory", "INTEGER")?;
+        add_column_if_missing(conn, "clients", "kdfIterations", "INTEGER")?;
+        add_column_if_missing(conn, "clients", "kdfParallelism", "INTEGER")?;
+        add_column_if_missing(conn, "clients", "cipherVersion", "INTEGER NOT NULL DEFAULT 0")?;
+        conn.execute("UPDATE clients SET kdfMemory = 19456, kdfIterations = 2, kdfParallelism = 1
+            WHERE kdfSalt IS NOT NULL AND kdfMemory IS NULL", [])?;
+        Ok(())
+    }
+
+    fn add_column_if_missing(conn: &Connection, table: &str, column: &str, column_type: &str) -> Result<()> {
+        let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+        let columns: Vec<String> = stmt.query_map([], |row| row.get(1))?.collect::<Result<_>>()?;
+
+        if !columns.iter().any(|name| name == column) {
+            conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, column_type), [])?;
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn columns(conn: &Connection, table: &str) -> Vec<String> {
+            let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table)).unwrap();
+            stmt.query_map([], |row| row.get(1)).unwrap().collect::<Result<_>>().unwrap()
+        }
+
+        #[test]
+        fn versions_are_sorted_without_gaps() {
+            for (index, migration) in MIGRATIONS.iter().enumerate() {
+                assert_eq!(migration.version as usize, index + 1);
+            }
+        }
+
+        #[test]
+        fn new_db_is_brought_to_latest_version() {
+            let conn = Connection::open_in_memory().unwrap();
+            migrate(&conn).unwrap();
+
+            assert_eq!(schema_version(&conn).unwrap(), latest_version());
+            assert!(columns(&conn, "clients").contains(&String::from("kdfSalt")));
+            assert!(columns(&conn, "trash").contains(&String::from("passwordNonce")));
+            assert!(columns(&conn, "clients").contains(&String::from("autoLockSeconds")));
+            assert!(columns(&conn, "accountFields").contains(&String::from("fieldValue")));
+            assert!(columns(&conn, "clients").contains(&String::from("kdfMemory")));
+            assert!(columns(&conn, "clients").contains(&String::from("cipherVersion")));
+        }
+
+        #[test]
+        fn migrating_twice_changes_nothing() {
+            let conn = Connection::open_in_memory().unwrap();
+            migrate(&conn).unwrap();
+            let before = columns(&conn, "accounts");
+            migrate(&conn).unwrap();
+
+            assert_eq!(columns(&conn, "accounts"), before);
+            assert_eq!(schema_version(&conn).unwrap(), latest_version());
+        }
+
+        // the schema manager.db was first created with, before user_version was set
+        #[test]
+        fn original_db_is_upgraded_without_losing_data() {
+            let conn = Connection::open_in_memory().unwrap();
+            conn.execute_batch(r#"
+            CREATE TABLE clients (clientId INTEGER PRIMARY KEY AUTOINCREMENT,
+                username TEXT NOT NULL UNIQUE, password TEXT NOT NULL);
+            CREATE TABLE accounts (accountId INTEGER PRIMARY KEY AUTOINCREMENT, clientId INTEGER,
+                accountName TEXT NOT NULL, accountUsername TEXT NOT NULL, accountPassword TEXT NOT NULL);
+            INSERT INTO clients (username, password) VALUES ('someone@example.com', 'hunter2hunter2');
+            INSERT INTO accounts (clientId, accountName, accountUsername, accountPassword)
+                VALUES (1, 'Bank', 'someone', 'secret');
+            "#).unwrap();
+
+            migrate(&conn).unwrap();
+
+            let (name, password, nonce): (String, String, Option<Vec<u8>>) = conn.query_row(
+                "SELECT accountName, accountPassword, passwordNonce FROM accounts WHERE clientId = 1", [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))).unwrap();
+            assert_eq!((name.as_str(), password.as_str(), nonce), ("Bank", "secret", None));
+            assert_eq!(schema_version(&conn).unwrap(), latest_version());
+        }
+
+        // copies opened before migrations existed already have the later columns
+        #[test]
+        fn unversioned_db_with_later_columns_is_upgraded() {
+            let conn = Connection::open_in_memory().unwrap();
+            create_base_tables(&conn).unwrap();
+            create_trash_table(&conn).unwrap();
+            add_encryption_columns(&conn).unwrap();
+
+            migrate(&conn).unwrap();
+            assert_eq!(schema_version(&conn).unwrap(), latest_version());
+        }
+
+        #[test]
+        fn newer_db_is_refused() {
+            let conn = Connection::open_in_memory().unwrap();
+            conn.pragma_update(None, "user_version", latest_version() + 1).unwrap();
+
+            assert!(matches!(migrate(&conn), Err(Error::UnsupportedSchema { .. })));
+            assert_eq!(schema_version(&conn).unwrap(), latest_version() + 1);
+        }
+    }
+}
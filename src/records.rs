@@ -3,28 +3,159 @@ where information must be recorded in the manager
 database or retrieved from it. */
 
 pub mod records {
-    use rand::Rng; // for random password generation
-    use rusqlite::{Connection, Result, Error};
+    use rand::rngs::OsRng; // the operating system's CSPRNG
+    use rand::{CryptoRng, RngCore}; // for random password generation
+    use std::fmt;
+    use std::path::Path;
+    use rusqlite::{named_params, params, Connection, OpenFlags, Row};
+    use rusqlite::types::{FromSql, FromSqlResult, ToSql, ToSqlOutput, Type, ValueRef};
+    use zeroize::Zeroize;
+    use crate::error::error::{Error, Result};
+    use crate::config::config::{active_vault, default_vault_path};
+    use crate::migrations::migrations::migrate;
+    use crate::secret::secret::Secret;
+    use crate::fields::fields::{CustomField, FieldKind};
+    use crate::crypto::crypto::{generate_salt, hash_master_password, verify_master_password, verify_dummy_password, KdfParams, VaultKey, Verification};
+
+    pub const DEFAULT_TRASH_RETENTION_DAYS: u32 = 30;
+    // seconds of inactivity before a session locks, for clients who have not chosen their own
+    pub const DEFAULT_AUTO_LOCK_SECONDS: u32 = 5 * 60;
+    pub const MIN_MASTER_PASSWORD_LEN: usize = 12;
+    pub const AMBIGUOUS_CHARS: &str = "0Oo1lI|";
+
+    // failed logins in a row allowed before each attempt has to wait, w/the
+    // wait doubling after every further failure until the username is locked out
+    pub const FREE_LOGIN_ATTEMPTS: u32 = 3;
+    pub const LOCKOUT_LOGIN_ATTEMPTS: u32 = 10;
+    pub const LOCKOUT_SECONDS: i64 = 15 * 60;
+
+    // one word per line. Diceware lists that put the dice roll before each
+    // word also work, since only the last field on each line is used.
+    pub const WORDLIST: &str = include_str!("wordlist.txt");
+    // passphrases w/the default number of words have at least this many bits
+    // of entropy, which is what 6 words of the EFF large wordlist give
+    pub const PASSPHRASE_MIN_BITS: f64 = 77.0;
+    const SECONDS_PER_DAY: i64 = 86400;
+    // the format of ciphertexts stored by this version, kept per client in
+    // cipherVersion: 1 means each one is bound to its row w/associated data
+    pub const CIPHER_VERSION: i64 = 1;
 
     
     #[derive(Debug)] // gives the derived trait to AccountInfo
+    #[allow(non_snake_case)] // accountId mirrors the column name in the db
     pub struct AccountInfo {
         pub account: String,
         pub username: String,
-        pub password: String,
-        pub accountId: Option<u64> // only applies to accounts pulled from db
+        pub password: Secret,
+        pub accountId: Option<AccountId>, // only applies to accounts pulled from db
+        pub fields: Vec<CustomField> // URLs, notes, and the like, in the order they were added
+    }
+
+    // declares a newtype over an INTEGER PRIMARY KEY column, so ids of different
+    // tables cannot be mixed up w/each other (or w/plain numbers)
+    macro_rules! id_type {
+        ($name:ident) => {
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            pub struct $name(pub i64);
+
+            impl fmt::Display for $name {
+                fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    write!(f, "{}", self.0)
+                }
+            }
+
+            impl std::str::FromStr for $name {
+                type Err = std::num::ParseIntError;
+
+                fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+                    s.parse().map($name)
+                }
+            }
+
+            impl ToSql for $name {
+                fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+                    Ok(ToSqlOutput::from(self.0))
+                }
+            }
+
+            impl FromSql for $name {
+                fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+                    i64::column_result(value).map($name)
+                }
+            }
+        };
+    }
+
+    id_type!(ClientId);
+    id_type!(AccountId);
+
+    impl AccountInfo {
+        // the id of an account read from the db. Entries that were never stored have none.
+        pub fn id(&self) -> Result<AccountId> {
+            self.accountId.ok_or_else(|| Error::NotFound(format!("stored entry named \"{}\"", self.account)))
+        }
     }
 
+    // outcome of a login attempt that reached the db
+    #[derive(Debug, PartialEq)]
+    pub enum AuthResult {
+        Authenticated(ClientId),
+        // the username or master password is wrong. retry_after is the number of
+        // seconds to wait before the next attempt, which is 0 for the first few.
+        Rejected { failed_attempts: u32, retry_after: i64 },
+        // too many recent failures, so the password was not even checked
+        LockedOut { retry_after: i64 }
+    }
+
+    // an account that was deleted but can still be restored until it is purged
+    #[derive(Debug)]
+    pub struct TrashedAccount {
+        pub entry: AccountInfo,
+        pub deleted_at: i64 // unix timestamp (seconds) of the deletion
+    }
+
+    // a logged in client. One can only be made by unlocking the vault w/the client's
+    // master password, so there is no User for "nobody", and logging out drops it
+    // (and its vault key).
     pub struct User {
-        // value is None if a user is not logged in or an unsigned int up to 255
-        pub client_id: Option<u8>
+        client_id: ClientId,
+        vault_key: VaultKey
     }
 
+    // minimum number of characters from each class in a generated password
     pub struct PasswordSpecs {
-        lower_letters: u8,
-        upper_letters: u8,
-        numbers: u8,
-        special_chars: u8
+        pub lower_letters: u8,
+        pub upper_letters: u8,
+        pub numbers: u8,
+        pub special_chars: u8,
+        pub excluded_chars: String, // never used in the password
+        pub avoid_ambiguous: bool // leaves out characters that are easy to mix up (0/O, l/1)
+    }
+
+    pub struct PassphraseSpecs {
+        pub words: u8,
+        pub separator: String,
+        pub capitalize: bool, // capitalizes the first letter of every word
+        pub add_digit: bool // appends a random digit to one random word
+    }
+
+    #[derive(Debug)]
+    pub enum GenerateError {
+        // the length cannot fit every required character
+        TooShort { minimum: usize },
+        // every character of a required class was excluded
+        EmptyClass(&'static str)
+    }
+
+    impl fmt::Display for GenerateError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                GenerateError::TooShort { minimum } =>
+                    write!(f, "the password must be at least {} characters long to meet the requirements", minimum),
+                GenerateError::EmptyClass(class) =>
+                    write!(f, "no {} are left to choose from after exclusions", class)
+            }
+        }
     }
 
     // a default trait helps avoid ownership-related 
@@ -39,132 +170,1019 @@ pub mod records {
             Self {
                 account: String::from("default_account"),
                 username: String::from("default_username"),
-                password: String::from("default_password"),
-                accountId: None
+                password: Secret::from("default_password"),
+                accountId: None,
+                fields: Vec::new()
             }
         }
     }
-    impl Default for User {
+    impl Default for PasswordSpecs {
         fn default() -> Self {
             Self {
-                client_id: None
+                lower_letters: 8,
+                upper_letters: 2,
+                numbers: 4,
+                special_chars: 4,
+                excluded_chars: String::new(),
+                avoid_ambiguous: false
             }
         }
     }
-    impl Default for PasswordSpecs {
+
+    impl Default for PassphraseSpecs {
         fn default() -> Self {
             Self {
-                lower_letters: 8,
-                upper_letters: 2,
-                numbers: 4,
-                special_chars: 4
+                words: default_passphrase_words(),
+                separator: String::from("-"),
+                capitalize: false,
+                add_digit: false
             }
         }
     }
 
-    // contains methods necessary for retrieving data from db
-    pub trait Retrieve {
-        fn lookup_user(&self, username_input: &str, password_input: &str) -> Result<(Option<u8>,)>;
-        fn set_client_id(&mut self, id: Option<u8>);
-        fn get_id(&self) -> u8;
-        fn get_accounts(&self, conn: &Connection, client_id: &str) -> Result<Vec<AccountInfo>>;
+    // contains methods necessary for logging clients in
+    pub trait Login: Sized {
+        fn lookup_user(conn: &Connection, username_input: &str, password_input: &str) -> Result<AuthResult>;
+        fn unlock_vault(conn: &Connection, client_id: ClientId, password_input: &str) -> Result<Self>;
+        fn log_in(conn: &Connection, username_input: &str, password_input: &str) -> Result<Self>;
     }
 
-    impl Retrieve for User {
+    impl Login for User {
+
+        // checks the master password of a user. Errors are only returned when the
+        // check itself could not be done, never for a wrong username or password.
+        fn lookup_user(conn: &Connection, username_input: &str, password_input: &str) -> Result<AuthResult> {
+            let now = unix_now();
 
-        fn lookup_user(&self, username_input: &str, password_input: &str) -> Result<(Option<u8>,)> {
-            let conn = Connection::open("manager.db")?;
+            let (failures, last_failure) = failed_logins(conn, username_input)?;
+            let retry_after = last_failure + login_delay(failures) - now;
+            if retry_after > 0 {
+                return Ok(AuthResult::LockedOut { retry_after });
+            }
         
-            let sql = "SELECT clientId FROM clients WHERE username = ? AND password = ?";
+            let sql = "SELECT clientId, password FROM clients WHERE username = ?";
         
             // final argument ensures that only one row at the most is found (as it should be anyways)
-            let client_id: u8 = conn.query_row(sql, [username_input, password_input],|row| row.get(0))?;
+            let (client_id, stored_hash): (ClientId, String) = match conn.query_row(sql, [username_input],
+                |row| Ok((row.get(0)?, row.get(1)?))) {
+                Ok(client) => client,
+                // unknown usernames count as failures too, and take as long to check as a wrong
+                // password does, so they look the same as wrong passwords
+                Err(rusqlite::Error::QueryReturnedNoRows) => {
+                    verify_dummy_password(password_input);
+                    return record_failed_login(conn, username_input, now);
+                }
+                Err(e) => return Err(e.into())
+            };
           
-            // will only be called if a user_id is found due to '?' error propagation
-            Ok((Some(client_id),))
+            match verify_master_password(password_input, &stored_hash) {
+                Verification::Valid => {}
+                Verification::ValidNeedsRehash => {
+                    // the hash is upgraded to the current parameters while the
+                    // plaintext master password is available
+                    let new_hash = hash_master_password(password_input)?;
+                    conn.execute("UPDATE clients SET password = ? WHERE clientId = ?", params![new_hash, client_id])?;
+                }
+                Verification::Invalid => return record_failed_login(conn, username_input, now)
+            }
+
+            conn.execute("DELETE FROM failedLogins WHERE username = ?", [username_input])?;
+            Ok(AuthResult::Authenticated(client_id))
         }
 
-        fn set_client_id(&mut self, id: Option<u8>) {
-            self.client_id = id;
+        // derives the vault key from the master password of a client that lookup_user
+        // authenticated. Clients created before encryption was added get a salt on their
+        // first login, and any of their passwords still stored in plaintext are encrypted.
+        // Vaults sealed w/older key costs or an older ciphertext format are resealed.
+        fn unlock_vault(conn: &Connection, client_id: ClientId, password_input: &str) -> Result<User> {
+            let (stored_salt, stored_params, cipher_version): (Option<Vec<u8>>, Option<KdfParams>, i64) = conn.query_row(
+                "SELECT kdfSalt, kdfMemory, kdfIterations, kdfParallelism, cipherVersion FROM clients WHERE clientId = ?",
+                [client_id], |row| {
+                    let params = match (row.get(1)?, row.get(2)?, row.get(3)?) {
+                        (Some(m_cost), Some(t_cost), Some(p_cost)) => Some(KdfParams { m_cost, t_cost, p_cost }),
+                        _ => None
+                    };
+                    Ok((row.get(0)?, params, row.get(4)?))
+                })?;
+
+            let tx = conn.unchecked_transaction()?;
+            let key = match stored_salt {
+                // nothing was encrypted before the client had a salt
+                None => {
+                    let salt = generate_salt();
+                    store_vault_format(&tx, &client_id, &salt)?;
+                    VaultKey::derive(password_input, &salt, &KdfParams::CURRENT)?
+                }
+                Some(salt) => {
+                    // salts stored before the costs were can only have used the legacy ones
+                    let params = stored_params.unwrap_or(KdfParams::LEGACY);
+                    let key = VaultKey::derive(password_input, &salt, &params)?;
+
+                    if params == KdfParams::CURRENT && cipher_version == CIPHER_VERSION {
+                        key
+                    } else {
+                        // the master password is only at hand now, so this is when the vault can be resealed
+                        let new_salt = generate_salt();
+                        let new_key = VaultKey::derive(password_input, &new_salt, &KdfParams::CURRENT)?;
+                        reseal_vault(&tx, &client_id, &key, cipher_version, &new_key)?;
+                        store_vault_format(&tx, &client_id, &new_salt)?;
+                        new_key
+                    }
+                }
+            };
+
+            for table in ["accounts", "trash"] {
+                encrypt_plaintext_rows(&tx, table, &key, &client_id)?;
+            }
+            tx.commit()?;
+
+            Ok(User { client_id, vault_key: key })
         }
 
-        // returns client id for current user, unless an id cannot be found
-        fn get_id(&self) -> u8 {
-            match self.client_id {
-                Some(value) => value,
-                None => 0
+        // looks the user up and unlocks their vault in one go, for callers that only
+        // need to know whether it worked (failed logins are still counted)
+        fn log_in(conn: &Connection, username_input: &str, password_input: &str) -> Result<User> {
+            match User::lookup_user(conn, username_input, password_input)? {
+                AuthResult::Authenticated(client_id) => User::unlock_vault(conn, client_id, password_input),
+                AuthResult::Rejected { .. } => Err(Error::BadCredentials),
+                AuthResult::LockedOut { retry_after } => Err(Error::LockedOut { retry_after })
             }
         }
-        fn get_accounts(&self, conn: &Connection, client_id: &str) -> Result<Vec<AccountInfo>> {
+    }
+
+    // contains methods necessary for retrieving data from db
+    pub trait Retrieve {
+        fn get_id(&self) -> ClientId;
+        fn get_vault_key(&self) -> &VaultKey;
+        fn get_accounts(&self, conn: &Connection) -> Result<Vec<AccountInfo>>;
+        fn find_accounts(&self, conn: &Connection, query: &str) -> Result<Vec<AccountInfo>>;
+        fn get_trash(&self, conn: &Connection) -> Result<Vec<TrashedAccount>>;
+    }
+
+    impl Retrieve for User {
+
+        fn get_id(&self) -> ClientId {
+            self.client_id
+        }
+
+        fn get_vault_key(&self) -> &VaultKey {
+            &self.vault_key
+        }
+
+        // only ever returns the accounts of the logged in user
+        fn get_accounts(&self, conn: &Connection) -> Result<Vec<AccountInfo>> {
     
-            let mut stmt = conn.prepare("SELECT accountName, accountUsername, accountPassword, accountId
+            let key = self.get_vault_key();
+            let mut stmt = conn.prepare("SELECT accountName, accountUsername, accountPassword, accountId, passwordNonce
             FROM accounts WHERE clientId = ?")?;
 
-            let accounts: Result<Vec<AccountInfo>, rusqlite::Error> = stmt.query_map([client_id], |row| {
+            let accounts: rusqlite::Result<Vec<AccountInfo>> = stmt.query_map([self.get_id()], |row| {
 
                 // use the iterator returned by query_map to create an instance of AccountInfo
+                let account_id: AccountId = row.get(3)?;
                 Ok(AccountInfo {
                     account: row.get(0)?,
                     username: row.get(1)?,
-                    password: row.get(2)?,
-                    accountId: row.get(3)?
+                    password: decrypt_password(row, key, 2, 4, &password_aad(&self.get_id(), &account_id))?,
+                    accountId: Some(account_id),
+                    fields: Vec::new() // filled in once every row has been read
                 })
             })?.collect(); // add each new instance of AccountInfo to the accounts vector
             
-            accounts
+            with_fields(conn, key, &self.get_id(), accounts?)
+        }
+
+        // accounts of the logged in user whose name or username matches the query, best
+        // matches first. Names that equal, start w/, or contain the query rank highest,
+        // then usernames that start w/or contain it, then fuzzy matches, which only need
+        // the query's characters in order (so "gthb" finds "GitHub"). Case is ignored.
+        fn find_accounts(&self, conn: &Connection, query: &str) -> Result<Vec<AccountInfo>> {
+
+            let key = self.get_vault_key();
+            let mut stmt = conn.prepare(r#"
+            SELECT accountName, accountUsername, accountPassword, accountId, passwordNonce,
+                CASE
+                    WHEN accountName LIKE :exact ESCAPE '\' THEN 0
+                    WHEN accountName LIKE :prefix ESCAPE '\' THEN 1
+                    WHEN accountName LIKE :substring ESCAPE '\' THEN 2
+                    WHEN accountUsername LIKE :prefix ESCAPE '\' THEN 3
+                    WHEN accountUsername LIKE :substring ESCAPE '\' THEN 4
+                    ELSE 5
+                END AS rank
+            FROM accounts
+            WHERE clientId = :client AND (accountName LIKE :fuzzy ESCAPE '\' OR accountUsername LIKE :fuzzy ESCAPE '\')
+            ORDER BY rank, length(accountName), accountName COLLATE NOCASE"#)?;
+
+            let patterns = SearchPatterns::new(query);
+            let params = named_params! {
+                ":exact": patterns.exact,
+                ":prefix": patterns.prefix,
+                ":substring": patterns.substring,
+                ":fuzzy": patterns.fuzzy,
+                ":client": self.get_id()
+            };
+            let accounts: rusqlite::Result<Vec<AccountInfo>> = stmt.query_map(params, |row| {
+                let account_id: AccountId = row.get(3)?;
+                Ok(AccountInfo {
+                    account: row.get(0)?,
+                    username: row.get(1)?,
+                    password: decrypt_password(row, key, 2, 4, &password_aad(&self.get_id(), &account_id))?,
+                    accountId: Some(account_id),
+                    fields: Vec::new() // filled in once every row has been read
+                })
+            })?.collect();
+
+            with_fields(conn, key, &self.get_id(), accounts?)
+        }
+
+        // same as get_accounts, but for accounts sitting in the trash
+        fn get_trash(&self, conn: &Connection) -> Result<Vec<TrashedAccount>> {
+
+            let key = self.get_vault_key();
+            let mut stmt = conn.prepare("SELECT accountName, accountUsername, accountPassword, accountId, passwordNonce, deletedAt
+            FROM trash WHERE clientId = ? ORDER BY deletedAt DESC")?;
+
+            let trash: rusqlite::Result<Vec<TrashedAccount>> = stmt.query_map([self.get_id()], |row| {
+                let account_id: AccountId = row.get(3)?;
+                Ok(TrashedAccount {
+                    entry: AccountInfo {
+                        account: row.get(0)?,
+                        username: row.get(1)?,
+                        password: decrypt_password(row, key, 2, 4, &password_aad(&self.get_id(), &account_id))?,
+                        accountId: Some(account_id),
+                        fields: Vec::new()
+                    },
+                    deleted_at: row.get(5)?
+                })
+            })?.collect();
+
+            let mut trash = trash?;
+            for trashed in trash.iter_mut() {
+                trashed.entry.fields = read_fields(conn, key, &self.get_id(), &trashed.entry.id()?)?;
+            }
+            Ok(trash)
         }
     }
 
     
     // contains methods necessary for transferring data to db 
     pub trait Transfer {
-       fn add_account(conn: &Connection, entry: AccountInfo, id: &u8) -> Result<()>;
+       fn add_account(conn: &Connection, entry: AccountInfo, id: &ClientId, key: &VaultKey) -> Result<()>;
+       fn update_account(conn: &Connection, entry: AccountInfo, id: &ClientId, key: &VaultKey) -> Result<()>;
+       fn delete_account(conn: &Connection, account_id: &AccountId, id: &ClientId) -> Result<()>;
+       fn restore_account(conn: &Connection, account_id: &AccountId, id: &ClientId) -> Result<()>;
+       fn purge_account(conn: &Connection, account_id: &AccountId, id: &ClientId) -> Result<()>;
+       fn purge_expired(conn: &Connection, retention_days: &u32, id: &ClientId) -> Result<usize>;
     }
 
     impl Transfer for AccountInfo {
-        fn add_account(conn: &Connection, entry: AccountInfo, id: &u8) -> Result<()> {
-            let stmt = "INSERT INTO accounts (accountName, accountUsername, accountPassword, clientId)
-            VALUES (?, ?, ?, ?)";
-            let client_id_string = id.to_string(); 
+        // the account and its fields are added in one transaction, so an entry is never stored w/only some of its fields
+        fn add_account(conn: &Connection, entry: AccountInfo, id: &ClientId, key: &VaultKey) -> Result<()> {
+            // the ciphertext is bound to the accountId, which is only known once the row exists
+            let stmt = "INSERT INTO accounts (accountName, accountUsername, accountPassword, clientId) VALUES (?, ?, X'', ?)";
+            let tx = conn.unchecked_transaction()?;
            
-            conn.execute(stmt, [entry.account, entry.username, entry.password, client_id_string])?;
+            tx.execute(stmt, params![entry.account, entry.username, id])?;
+            let account_id = AccountId(tx.last_insert_rowid());
+            let (ciphertext, nonce) = key.encrypt(entry.password.expose(), &password_aad(id, &account_id))?;
+            tx.execute("UPDATE accounts SET accountPassword = ?, passwordNonce = ? WHERE accountId = ?",
+                params![ciphertext, nonce, account_id])?;
+            write_fields(&tx, key, id, &account_id, &entry.fields)?;
 
+            tx.commit()?;
             Ok(()) // only an indication of success needs to be sent back
         }
+
+        // overwrites the stored values of an existing account, and replaces its fields
+        // w/the entry's. The clientId is part of the WHERE clause so a client can never
+        // change another client's rows (or their fields, since nothing is committed then).
+        fn update_account(conn: &Connection, entry: AccountInfo, id: &ClientId, key: &VaultKey) -> Result<()> {
+            let stmt = "UPDATE accounts SET accountName = ?, accountUsername = ?, accountPassword = ?, passwordNonce = ?
+            WHERE accountId = ? AND clientId = ?";
+            let account_id = entry.id()?;
+            let (ciphertext, nonce) = key.encrypt(entry.password.expose(), &password_aad(id, &account_id))?;
+            let tx = conn.unchecked_transaction()?;
+
+            let rows_changed = tx.execute(stmt, params![entry.account, entry.username, ciphertext, nonce, account_id, id])?;
+
+            expect_one_row(rows_changed, "entry")?;
+            write_fields(&tx, key, id, &account_id, &entry.fields)?;
+            tx.commit()?;
+            Ok(())
+        }
+
+        // moves an account into the trash instead of removing it outright, so it
+        // can still be restored. Both statements run in one transaction so an
+        // account is never lost or duplicated if one of them fails.
+        fn delete_account(conn: &Connection, account_id: &AccountId, id: &ClientId) -> Result<()> {
+            let tx = conn.unchecked_transaction()?;
+
+            tx.execute("INSERT INTO trash (accountId, clientId, accountName, accountUsername, accountPassword, passwordNonce)
+            SELECT accountId, clientId, accountName, accountUsername, accountPassword, passwordNonce
+            FROM accounts WHERE accountId = ? AND clientId = ?", params![account_id, id])?;
+            let rows_changed = tx.execute("DELETE FROM accounts WHERE accountId = ? AND clientId = ?", params![account_id, id])?;
+
+            // nothing was moved, so there is nothing to commit
+            expect_one_row(rows_changed, "entry")?;
+            tx.commit()?;
+            Ok(())
+        }
+
+        // moves an account out of the trash, keeping its original accountId
+        fn restore_account(conn: &Connection, account_id: &AccountId, id: &ClientId) -> Result<()> {
+            let tx = conn.unchecked_transaction()?;
+
+            tx.execute("INSERT INTO accounts (accountId, clientId, accountName, accountUsername, accountPassword, passwordNonce)
+            SELECT accountId, clientId, accountName, accountUsername, accountPassword, passwordNonce
+            FROM trash WHERE accountId = ? AND clientId = ?", params![account_id, id])?;
+            let rows_changed = tx.execute("DELETE FROM trash WHERE accountId = ? AND clientId = ?", params![account_id, id])?;
+
+            // nothing was moved, so there is nothing to commit
+            expect_one_row(rows_changed, "entry in the trash")?;
+            tx.commit()?;
+            Ok(())
+        }
+
+        // permanently removes a single account from the trash, along w/its fields
+        fn purge_account(conn: &Connection, account_id: &AccountId, id: &ClientId) -> Result<()> {
+            let tx = conn.unchecked_transaction()?;
+
+            tx.execute("DELETE FROM accountFields WHERE accountId IN
+            (SELECT accountId FROM trash WHERE accountId = ? AND clientId = ?)", params![account_id, id])?;
+            let rows_changed = tx.execute("DELETE FROM trash WHERE accountId = ? AND clientId = ?", params![account_id, id])?;
+
+            expect_one_row(rows_changed, "entry in the trash")?;
+            tx.commit()?;
+            Ok(())
+        }
+
+        // permanently removes every account (and its fields) that has been
+        // in the trash for longer than the retention period
+        fn purge_expired(conn: &Connection, retention_days: &u32, id: &ClientId) -> Result<usize> {
+            let tx = conn.unchecked_transaction()?;
+
+            tx.execute("DELETE FROM accountFields WHERE accountId IN (SELECT accountId FROM trash
+            WHERE clientId = ? AND deletedAt <= strftime('%s', 'now') - ? * 86400)", params![id, retention_days])?;
+            let purged = tx.execute("DELETE FROM trash WHERE clientId = ? AND deletedAt <= strftime('%s', 'now') - ? * 86400",
+                params![id, retention_days])?;
+
+            tx.commit()?;
+            Ok(purged)
+        }
+    }
+
+    // contains methods necessary for creating new clients
+    pub trait Register {
+        fn username_taken(conn: &Connection, username_input: &str) -> Result<bool>;
+        fn register_user(conn: &Connection, username_input: &str, password_input: &str) -> Result<ClientId>;
+    }
+
+    impl Register for User {
+        fn username_taken(conn: &Connection, username_input: &str) -> Result<bool> {
+            Ok(conn.query_row("SELECT EXISTS(SELECT 1 FROM clients WHERE username = ?)", [username_input], |row| row.get(0))?)
+        }
+
+        // stores the new client w/a hash of their master password, and the salt and
+        // costs their vault key will be derived w/. Returns the new clientId.
+        // The UNIQUE constraint on username makes this fail if the name is taken.
+        fn register_user(conn: &Connection, username_input: &str, password_input: &str) -> Result<ClientId> {
+            let password_hash = hash_master_password(password_input)?;
+
+            // a client is never stored w/o the format of their vault, which would make it look legacy
+            let tx = conn.unchecked_transaction()?;
+            tx.execute("INSERT INTO clients (username, password) VALUES (?, ?)", params![username_input, password_hash])
+                .map_err(|e| match Error::from(e) {
+                    Error::Duplicate(_) => Error::Duplicate(format!("user named \"{}\"", username_input)),
+                    e => e
+                })?;
+            let client_id = ClientId(tx.last_insert_rowid());
+            store_vault_format(&tx, &client_id, &generate_salt())?;
+            tx.commit()?;
+
+            Ok(client_id)
+        }
+    }
+
+    // contains the preferences each client keeps in the db
+    pub trait Settings {
+        fn get_auto_lock(&self, conn: &Connection) -> Result<u32>;
+        fn set_auto_lock(&self, conn: &Connection, seconds: u32) -> Result<()>;
+    }
+
+    impl Settings for User {
+        // seconds of inactivity before the logged in client's session locks. 0 means never.
+        fn get_auto_lock(&self, conn: &Connection) -> Result<u32> {
+            let seconds: Option<u32> = conn.query_row("SELECT autoLockSeconds FROM clients WHERE clientId = ?",
+                [self.get_id()], |row| row.get(0))
+                .map_err(|e| match Error::from(e) {
+                    Error::NotFound(_) => Error::NotFound(String::from("logged in user")),
+                    e => e
+                })?;
+            Ok(seconds.unwrap_or(DEFAULT_AUTO_LOCK_SECONDS))
+        }
+
+        fn set_auto_lock(&self, conn: &Connection, seconds: u32) -> Result<()> {
+            let rows = conn.execute("UPDATE clients SET autoLockSeconds = ? WHERE clientId = ?", params![seconds, self.get_id()])?;
+            expect_one_row(rows, "logged in user")
+        }
+    }
+
+    // returns every rule the master password breaks, so an empty vector means it is strong enough
+    pub fn check_master_password(password: &str, username: &str) -> Vec<String> {
+        let mut problems: Vec<String> = Vec::new();
+
+        if password.chars().count() < MIN_MASTER_PASSWORD_LEN {
+            problems.push(format!("It must be at least {} characters long.", MIN_MASTER_PASSWORD_LEN));
+        }
+
+        let character_classes = [
+            password.chars().any(|c| c.is_ascii_lowercase()),
+            password.chars().any(|c| c.is_ascii_uppercase()),
+            password.chars().any(|c| c.is_ascii_digit()),
+            password.chars().any(|c| !c.is_ascii_alphanumeric())
+        ];
+        if character_classes.iter().filter(|&&present| present).count() < 3 {
+            problems.push(String::from("It must use at least 3 of: lowercase letters, uppercase letters, numbers, symbols."));
+        }
+
+        if !username.is_empty() && password.to_lowercase().contains(&username.to_lowercase()) {
+            problems.push(String::from("It must not contain your username."));
+        }
+
+        problems
+    }
+
+    // number of days a deleted account stays in the trash before it is purged.
+    // Can be changed by setting the PASSWORD_MANAGER_TRASH_DAYS environment variable.
+    pub fn trash_retention_days() -> u32 {
+        std::env::var("PASSWORD_MANAGER_TRASH_DAYS")
+            .ok()
+            .and_then(|days| days.parse::<u32>().ok())
+            .unwrap_or(DEFAULT_TRASH_RETENTION_DAYS)
+    }
+
+    // seconds a username has to wait after its last failed login before trying
+    // again: nothing for the first few failures, then 1, 2, 4, ... up to the lockout
+    pub fn login_delay(failed_attempts: u32) -> i64 {
+        if failed_attempts >= LOCKOUT_LOGIN_ATTEMPTS {
+            LOCKOUT_SECONDS
+        } else if failed_attempts >= FREE_LOGIN_ATTEMPTS {
+            1 << (failed_attempts - FREE_LOGIN_ATTEMPTS)
+        } else {
+            0
+        }
+    }
+
+    // failures in a row and the time of the last one, or zeros if there are none
+    fn failed_logins(conn: &Connection, username: &str) -> Result<(u32, i64)> {
+        let sql = "SELECT failures, lastFailure FROM failedLogins WHERE username = ?";
+        match conn.query_row(sql, [username], |row| Ok((row.get(0)?, row.get(1)?))) {
+            Ok(failed) => Ok(failed),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok((0, 0)),
+            Err(e) => Err(e.into())
+        }
+    }
+
+    fn record_failed_login(conn: &Connection, username: &str, now: i64) -> Result<AuthResult> {
+        conn.execute("INSERT INTO failedLogins (username, failures, lastFailure) VALUES (?, 1, ?)
+            ON CONFLICT(username) DO UPDATE SET failures = failures + 1, lastFailure = excluded.lastFailure",
+            params![username, now])?;
+
+        let (failed_attempts, _) = failed_logins(conn, username)?;
+        Ok(AuthResult::Rejected { failed_attempts, retry_after: login_delay(failed_attempts) })
+    }
+
+    // current unix timestamp in seconds
+    fn unix_now() -> i64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| duration.as_secs() as i64)
+            .unwrap_or_default()
+    }
+
+    // whole days left before a trashed account gets purged
+    pub fn days_until_purge(deleted_at: i64, retention_days: u32) -> i64 {
+        let now = unix_now();
+        let purge_at = deleted_at + i64::from(retention_days) * SECONDS_PER_DAY;
+
+        ((purge_at - now) / SECONDS_PER_DAY).max(0)
     }
  
-    pub fn generate_password(length: u8) -> String {
+    // any cryptographically secure random number generator. Generation always
+    // uses OsRng outside of tests, which can pass in a seeded generator instead.
+    pub trait SecureRng: RngCore + CryptoRng {}
+    impl<R: RngCore + CryptoRng> SecureRng for R {}
 
-        // Define a vector containing characters for password generation
-        let chars_pool: Vec<char> = (b'A'..=b'Z')
-        
-            // adding b means everything is accessed by its ascii values
-            // the ranges shown are found using a chart of ascii values
-            .chain(b'a'..=b'z') 
-            .chain(b'0'..=b'9')
-            .chain(b'!'..=b'/')
-            .chain(b':'..=b'@')
-            .chain(b'['..=b'`')
-            .chain(b'{'..=b'~')
-            // convert every byte into a char
-            .map(|c| c as char)
-            .collect(); // place all char values in the chars_pool vector
-        
-        // initializes a random number generator
-        let mut rng = rand::thread_rng();
+    // returns a uniformly distributed index below len. Random values that fall in
+    // the partial range at the top of u32 are rejected and drawn again, because
+    // taking them modulo len would make the lower indexes slightly more likely.
+    pub fn uniform_index<R: SecureRng>(rng: &mut R, len: usize) -> usize {
+        assert!(len > 0 && len <= u32::MAX as usize, "index range must be within 1..=u32::MAX");
+        let len = len as u32;
+        let zone = u32::MAX - (u32::MAX % len); // largest multiple of len that fits
 
-        // generates password w/specified length by repeatedly selecting random characters
-        // from the chars_pool vector and collecting them into the password String
-        let password: String = (0..length).map(|_| chars_pool[rng.gen_range(0..chars_pool.len())]).collect();
-        password
+        loop {
+            let value = rng.next_u32();
+            if value < zone {
+                return (value % len) as usize;
+            }
+        }
+    }
+
+    // generates a password w/the specified length that contains at least the
+    // minimum number of characters from each class listed in specs
+    pub fn generate_password(length: u8, specs: &PasswordSpecs) -> Result<Secret, GenerateError> {
+        generate_password_with(length, specs, &mut OsRng)
+    }
+
+    pub fn generate_password_with<R: SecureRng>(length: u8, specs: &PasswordSpecs, rng: &mut R) -> Result<Secret, GenerateError> {
+
+        // adding b means everything is accessed by its ascii values
+        // the ranges shown are found using a chart of ascii values
+        let specials = (b'!'..=b'/').chain(b':'..=b'@').chain(b'['..=b'`').chain(b'{'..=b'~');
+        let classes: [(&'static str, Vec<u8>, u8); 4] = [
+            ("lowercase letters", (b'a'..=b'z').collect(), specs.lower_letters),
+            ("uppercase letters", (b'A'..=b'Z').collect(), specs.upper_letters),
+            ("numbers", (b'0'..=b'9').collect(), specs.numbers),
+            ("special characters", specials.collect(), specs.special_chars)
+        ];
+
+        let minimum: usize = classes.iter().map(|(_, _, min)| *min as usize).sum();
+        if (length as usize) < minimum {
+            return Err(GenerateError::TooShort { minimum });
+        }
+
+        // convert every allowed byte of each class into a char
+        let mut pools: Vec<(&'static str, Vec<char>, u8)> = Vec::new();
+        for (name, class, min) in classes {
+            let pool: Vec<char> = class.into_iter()
+                .map(|c| c as char)
+                .filter(|c| !specs.excluded_chars.contains(*c))
+                .filter(|c| !(specs.avoid_ambiguous && AMBIGUOUS_CHARS.contains(*c)))
+                .collect();
+            if pool.is_empty() && min > 0 {
+                return Err(GenerateError::EmptyClass(name));
+            }
+            pools.push((name, pool, min));
+        }
+
+        // every allowed character can fill the spots left after the minimums
+        let chars_pool: Vec<char> = pools.iter().flat_map(|(_, pool, _)| pool.iter().copied()).collect();
+        if chars_pool.is_empty() && length > 0 {
+            return Err(GenerateError::EmptyClass("characters"));
+        }
+
+        let mut password: Vec<char> = Vec::with_capacity(length as usize);
+        for (_, pool, min) in pools.iter() {
+            password.extend((0..*min).map(|_| pool[uniform_index(rng, pool.len())]));
+        }
+        while password.len() < length as usize {
+            password.push(chars_pool[uniform_index(rng, chars_pool.len())]);
+        }
+
+        // the required characters were added first, so they are shuffled
+        // (Fisher-Yates) to keep their positions from being predictable
+        for i in (1..password.len()).rev() {
+            password.swap(i, uniform_index(rng, i + 1));
+        }
+        let secret = Secret::new(password.iter().collect());
+        password.zeroize();
+        Ok(secret)
     } 
     
-    pub fn open_database() -> Result<Connection, Error> {
+    // picks words at random from the bundled wordlist. Returns the passphrase
+    // along w/its entropy in bits, which assumes an attacker knows the
+    // wordlist and the specs used, so only the random choices count.
+    pub fn generate_passphrase(specs: &PassphraseSpecs) -> (Secret, f64) {
+        generate_passphrase_with(specs, &mut OsRng)
+    }
+
+    // the words of the bundled wordlist, w/o any dice rolls in front of them
+    fn wordlist_words() -> Vec<&'static str> {
+        WORDLIST.lines().filter_map(|line| line.split_whitespace().last()).collect()
+    }
+
+    // the fewest words that reach PASSPHRASE_MIN_BITS w/the bundled wordlist,
+    // so a smaller list gets longer passphrases rather than weaker ones
+    pub fn default_passphrase_words() -> u8 {
+        let bits_per_word = (wordlist_words().len() as f64).log2();
+        (PASSPHRASE_MIN_BITS / bits_per_word).ceil() as u8
+    }
+
+    pub fn generate_passphrase_with<R: SecureRng>(specs: &PassphraseSpecs, rng: &mut R) -> (Secret, f64) {
+        let wordlist = wordlist_words();
+
+        let mut words: Vec<String> = (0..specs.words)
+            .map(|_| wordlist[uniform_index(rng, wordlist.len())].to_string())
+            .collect();
+        let mut entropy = f64::from(specs.words) * (wordlist.len() as f64).log2();
+
+        if specs.capitalize {
+            for word in words.iter_mut() {
+                let mut chars = word.chars();
+                if let Some(first) = chars.next() {
+                    *word = first.to_uppercase().chain(chars).collect();
+                }
+            }
+        }
+
+        if specs.add_digit && !words.is_empty() {
+            let position = uniform_index(rng, words.len());
+            words[position].push(char::from(b'0' + uniform_index(rng, 10) as u8));
+            entropy += 10f64.log2() + (words.len() as f64).log2();
+        }
+
+        let passphrase = Secret::new(words.join(&specs.separator));
+        words.zeroize();
+        (passphrase, entropy)
+    }
+
+    // LIKE patterns for a search. The query's own % and _ are escaped
+    // so they only ever match themselves.
+    #[derive(Debug, PartialEq)]
+    pub struct SearchPatterns {
+        pub exact: String,
+        pub prefix: String,
+        pub substring: String,
+        pub fuzzy: String // the query's characters in order w/anything between them
+    }
+
+    impl SearchPatterns {
+        pub fn new(query: &str) -> Self {
+            let escaped: Vec<String> = query.trim().chars().map(|c| match c {
+                '%' | '_' | '\\' => format!("\\{}", c),
+                _ => c.to_string()
+            }).collect();
+
+            SearchPatterns {
+                exact: escaped.concat(),
+                prefix: format!("{}%", escaped.concat()),
+                substring: format!("%{}%", escaped.concat()),
+                fuzzy: format!("%{}%", escaped.join("%"))
+            }
+        }
+    }
+
+    // opens the active vault
+    pub fn open_database() -> Result<Connection> {
+        open_vault(&active_vault())
+    }
+
+    // same as open_database, but for the vault at path. Only the default vault is
+    // created if it does not exist yet, so a mistyped name or path is never made
+    // into a new, empty vault. Others have to be made w/create_vault first.
+    pub fn open_vault(path: &Path) -> Result<Connection> {
+        if path == default_vault_path() {
+            return create_vault(path);
+        }
+        if !path.is_file() {
+            return Err(vault_not_found(path));
+        }
+
+        // older db files are upgraded to the current schema before anything else reads them
+        let conn: Connection = Connection::open_with_flags(path, OpenFlags::default() - OpenFlags::SQLITE_OPEN_CREATE)?;
+        migrate(&conn)?;
+        Ok(conn)
+    }
+
+    // opens the vault at path, creating it (and its directory) if it does not exist yet
+    pub fn create_vault(path: &Path) -> Result<Connection> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
        
-        let conn: Connection = Connection::open("manager.db")?;
+        let conn: Connection = Connection::open(path)?;
+        migrate(&conn)?;
         Ok(conn)
     }
 
+    pub fn vault_not_found(path: &Path) -> Error {
+        Error::NotFound(format!("vault at {}", path.display()))
+    }
+
+    // rows without a nonce were stored before encryption was added,
+    // so their passwords are still plaintext and get encrypted in place
+    fn encrypt_plaintext_rows(conn: &Connection, table: &str, key: &VaultKey, id: &ClientId) -> Result<()> {
+        let mut stmt = conn.prepare(&format!("SELECT accountId, accountPassword FROM {}
+            WHERE clientId = ? AND passwordNonce IS NULL", table))?;
+        let plaintext_rows: Vec<(AccountId, Secret)> = stmt.query_map([id], |row| Ok((row.get(0)?, Secret::new(row.get(1)?))))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        for (account_id, password) in plaintext_rows {
+            let (ciphertext, nonce) = key.encrypt(password.expose(), &password_aad(id, &account_id))?;
+            conn.execute(&format!("UPDATE {} SET accountPassword = ?, passwordNonce = ? WHERE accountId = ?", table),
+                params![ciphertext, nonce, account_id])?;
+        }
+        Ok(())
     }
 
-        
\ No newline at end of file
+    // the associated data each ciphertext is sealed w/, which names the client, row, and
+    // column it belongs in. A trashed entry keeps its accountId, so it still decrypts there.
+    fn password_aad(client_id: &ClientId, account_id: &AccountId) -> Vec<u8> {
+        format!("client {} account {} accountPassword", client_id, account_id).into_bytes()
+    }
+
+    fn field_aad(client_id: &ClientId, account_id: &AccountId, field_id: i64) -> Vec<u8> {
+        format!("client {} account {} field {} fieldValue", client_id, account_id, field_id).into_bytes()
+    }
+
+    fn store_vault_format(conn: &Connection, client_id: &ClientId, salt: &[u8]) -> Result<()> {
+        let params = KdfParams::CURRENT;
+        conn.execute("UPDATE clients SET kdfSalt = ?, kdfMemory = ?, kdfIterations = ?, kdfParallelism = ?, cipherVersion = ?
+            WHERE clientId = ?", params![salt, params.m_cost, params.t_cost, params.p_cost, CIPHER_VERSION, client_id])?;
+        Ok(())
+    }
+
+    // re-encrypts every password and field value of a client w/new_key, bound to where
+    // each one is stored. Ciphertexts in format 0 were sealed w/o associated data.
+    fn reseal_vault(conn: &Connection, client_id: &ClientId, old_key: &VaultKey, old_version: i64, new_key: &VaultKey) -> Result<()> {
+        let old_aad = |aad: &[u8]| if old_version == 0 { Vec::new() } else { aad.to_vec() };
+
+        for table in ["accounts", "trash"] {
+            let mut stmt = conn.prepare(&format!("SELECT accountId, accountPassword, passwordNonce FROM {}
+                WHERE clientId = ? AND passwordNonce IS NOT NULL", table))?;
+            let sealed: Vec<(AccountId, Vec<u8>, Vec<u8>)> = stmt.query_map([client_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+                .collect::<rusqlite::Result<_>>()?;
+
+            for (account_id, ciphertext, nonce) in sealed {
+                let aad = password_aad(client_id, &account_id);
+                let password = old_key.decrypt(&ciphertext, &nonce, &old_aad(&aad))?;
+                let (ciphertext, nonce) = new_key.encrypt(password.expose(), &aad)?;
+                conn.execute(&format!("UPDATE {} SET accountPassword = ?, passwordNonce = ? WHERE accountId = ?", table),
+                    params![ciphertext, nonce, account_id])?;
+            }
+        }
+
+        let mut stmt = conn.prepare("SELECT fieldId, accountId, fieldValue, valueNonce FROM accountFields WHERE accountId IN
+            (SELECT accountId FROM accounts WHERE clientId = ?1 UNION SELECT accountId FROM trash WHERE clientId = ?1)")?;
+        let sealed: Vec<(i64, AccountId, Vec<u8>, Vec<u8>)> = stmt.query_map([client_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        for (field_id, account_id, ciphertext, nonce) in sealed {
+            let aad = field_aad(client_id, &account_id, field_id);
+            let value = old_key.decrypt(&ciphertext, &nonce, &old_aad(&aad))?;
+            let (ciphertext, nonce) = new_key.encrypt(value.expose(), &aad)?;
+            conn.execute("UPDATE accountFields SET fieldValue = ?, valueNonce = ? WHERE fieldId = ?", params![ciphertext, nonce, field_id])?;
+        }
+        Ok(())
+    }
+
+    // reads an encrypted password (or field value) column together with the nonce it was sealed with
+    // (this runs inside row mapping, so a failure is wrapped in a rusqlite error)
+    fn decrypt_password(row: &Row, key: &VaultKey, password_index: usize, nonce_index: usize, aad: &[u8]) -> rusqlite::Result<Secret> {
+        let ciphertext: Vec<u8> = row.get(password_index)?;
+        let nonce: Vec<u8> = row.get(nonce_index)?;
+
+        key.decrypt(&ciphertext, &nonce, aad)
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(password_index, Type::Blob, Box::new(e)))
+    }
+
+    // fills in the fields of accounts that were just read. Their ids came from
+    // queries limited to one client, so only that client's fields are read.
+    fn with_fields(conn: &Connection, key: &VaultKey, client_id: &ClientId, mut accounts: Vec<AccountInfo>) -> Result<Vec<AccountInfo>> {
+        for account in accounts.iter_mut() {
+            account.fields = read_fields(conn, key, client_id, &account.id()?)?;
+        }
+        Ok(accounts)
+    }
+
+    fn read_fields(conn: &Connection, key: &VaultKey, client_id: &ClientId, account_id: &AccountId) -> Result<Vec<CustomField>> {
+        let mut stmt = conn.prepare_cached("SELECT fieldId, fieldLabel, fieldType, fieldValue, valueNonce
+        FROM accountFields WHERE accountId = ? ORDER BY fieldId")?;
+
+        let fields: rusqlite::Result<Vec<CustomField>> = stmt.query_map([account_id], |row| {
+            Ok(CustomField {
+                label: row.get(1)?,
+                kind: row.get::<_, FieldKind>(2)?,
+                value: decrypt_password(row, key, 3, 4, &field_aad(client_id, account_id, row.get(0)?))?
+            })
+        })?.collect();
+
+        Ok(fields?)
+    }
+
+    // replaces every stored field of an account w/fields. Like passwords, each value is
+    // bound to its fieldId, so it is only encrypted once the row has been added.
+    fn write_fields(conn: &Connection, key: &VaultKey, client_id: &ClientId, account_id: &AccountId, fields: &[CustomField]) -> Result<()> {
+        conn.execute("DELETE FROM accountFields WHERE accountId = ?", [account_id])?;
+
+        for field in fields {
+            conn.execute("INSERT INTO accountFields (accountId, fieldLabel, fieldType, fieldValue, valueNonce)
+            VALUES (?, ?, ?, X'', X'')", params![account_id, field.label, field.kind])?;
+            let field_id = conn.last_insert_rowid();
+            let (ciphertext, nonce) = key.encrypt(field.value.expose(), &field_aad(client_id, account_id, field_id))?;
+            conn.execute("UPDATE accountFields SET fieldValue = ?, valueNonce = ? WHERE fieldId = ?", params![ciphertext, nonce, field_id])?;
+        }
+        Ok(())
+    }
+
+    // statements that target a single account change one row, or none
+    // if the account does not exist or belongs to another client
+    fn expect_one_row(rows_changed: usize, what: &str) -> Result<()> {
+        match rows_changed {
+            0 => Err(Error::NotFound(String::from(what))),
+            _ => Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        fn seeded(seed: u64) -> StdRng {
+            StdRng::seed_from_u64(seed)
+        }
+
+        fn no_minimums() -> PasswordSpecs {
+            PasswordSpecs {
+                lower_letters: 0,
+                upper_letters: 0,
+                numbers: 0,
+                special_chars: 0,
+                ..PasswordSpecs::default()
+            }
+        }
+
+        #[test]
+        fn uniform_index_stays_in_range() {
+            let mut rng = seeded(1);
+            for len in [1, 2, 3, 7, 10, 94, 2048] {
+                for _ in 0..1000 {
+                    assert!(uniform_index(&mut rng, len) < len);
+                }
+            }
+        }
+
+        #[test]
+        fn uniform_index_is_evenly_distributed() {
+            let mut rng = seeded(2);
+            let mut counts = [0u32; 6];
+            for _ in 0..60_000 {
+                counts[uniform_index(&mut rng, 6)] += 1;
+            }
+            // expected 10,000 each; 5% is far outside normal variation
+            for count in counts {
+                assert!((9_500..=10_500).contains(&count), "{:?}", counts);
+            }
+        }
+
+        #[test]
+        fn same_seed_generates_same_password() {
+            let specs = PasswordSpecs::default();
+            let first = generate_password_with(24, &specs, &mut seeded(3)).unwrap();
+            let second = generate_password_with(24, &specs, &mut seeded(3)).unwrap();
+            assert_eq!(first, second);
+        }
+
+        #[test]
+        fn password_has_requested_length() {
+            let mut rng = seeded(4);
+            for length in [18, 19, 32, 64, 255] {
+                let password = generate_password_with(length, &PasswordSpecs::default(), &mut rng).unwrap();
+                assert_eq!(password.expose().chars().count(), length as usize);
+            }
+            assert_eq!(generate_password_with(0, &no_minimums(), &mut rng).unwrap().expose(), "");
+        }
+
+        #[test]
+        fn password_shorter_than_minimums_is_rejected() {
+            // the defaults require 8 + 2 + 4 + 4 characters
+            match generate_password_with(17, &PasswordSpecs::default(), &mut seeded(5)) {
+                Err(GenerateError::TooShort { minimum }) => assert_eq!(minimum, 18),
+                other => panic!("expected TooShort, got {:?}", other)
+            }
+        }
+
+        #[test]
+        fn password_meets_class_minimums() {
+            let specs = PasswordSpecs::default();
+            for seed in 0..200 {
+                let password = generate_password_with(18, &specs, &mut seeded(seed)).unwrap();
+                assert!(password.expose().chars().filter(|c| c.is_ascii_lowercase()).count() >= 8);
+                assert!(password.expose().chars().filter(|c| c.is_ascii_uppercase()).count() >= 2);
+                assert!(password.expose().chars().filter(|c| c.is_ascii_digit()).count() >= 4);
+                assert!(password.expose().chars().filter(|c| c.is_ascii_punctuation()).count() >= 4);
+            }
+        }
+
+        #[test]
+        fn password_skips_excluded_and_ambiguous_chars() {
+            let specs = PasswordSpecs {
+                excluded_chars: String::from("abc$%"),
+                avoid_ambiguous: true,
+                ..PasswordSpecs::default()
+            };
+            let mut rng = seeded(6);
+            for _ in 0..200 {
+                let password = generate_password_with(40, &specs, &mut rng).unwrap();
+                assert!(!password.expose().chars().any(|c| specs.excluded_chars.contains(c) || AMBIGUOUS_CHARS.contains(c)));
+            }
+        }
+
+        #[test]
+        fn required_class_cannot_be_fully_excluded() {
+            let specs = PasswordSpecs {
+                excluded_chars: String::from("0123456789"),
+                ..PasswordSpecs::default()
+            };
+            assert!(matches!(generate_password_with(20, &specs, &mut seeded(7)), Err(GenerateError::EmptyClass("numbers"))));
+        }
+
+        #[test]
+        fn password_chars_are_evenly_distributed() {
+            let mut rng = seeded(8);
+            let mut counts = std::collections::HashMap::new();
+            for _ in 0..400 {
+                for c in generate_password_with(255, &no_minimums(), &mut rng).unwrap().expose().chars() {
+                    *counts.entry(c).or_insert(0u32) += 1;
+                }
+            }
+            // 94 printable ASCII characters, ~1,085 draws each
+            assert_eq!(counts.len(), 94);
+            let expected = (400 * 255) as f64 / 94.0;
+            for (c, count) in counts {
+                assert!((count as f64 - expected).abs() < expected * 0.15, "{} appeared {} times", c, count);
+            }
+        }
+
+        // repeated words would make passphrases weaker than the entropy reported for them
+        #[test]
+        fn wordlist_words_are_unique() {
+            let words: Vec<&str> = WORDLIST.lines().filter_map(|line| line.split_whitespace().last()).collect();
+            let unique: std::collections::HashSet<&str> = words.iter().copied().collect();
+
+            assert_eq!(words.len(), WORDLIST.lines().count());
+            assert_eq!(unique.len(), words.len());
+        }
+
+        #[test]
+        fn default_passphrases_reach_the_minimum_entropy() {
+            let specs = PassphraseSpecs::default();
+            let (passphrase, entropy) = generate_passphrase_with(&specs, &mut seeded(11));
+
+            assert_eq!(passphrase.expose().split('-').count(), usize::from(specs.words));
+            assert!(entropy >= PASSPHRASE_MIN_BITS, "{}", entropy);
+            // one word fewer would not be enough
+            assert!(entropy - (wordlist_words().len() as f64).log2() < PASSPHRASE_MIN_BITS);
+        }
+
+        #[test]
+        fn passphrase_follows_specs() {
+            let specs = PassphraseSpecs {
+                words: 5,
+                separator: String::from("."),
+                capitalize: true,
+                add_digit: true
+            };
+            let (passphrase, entropy) = generate_passphrase_with(&specs, &mut seeded(10));
+            let words: Vec<&str> = passphrase.expose().split('.').collect();
+
+            assert_eq!(words.len(), 5);
+            assert!(words.iter().all(|word| word.chars().next().unwrap().is_uppercase()));
+            assert_eq!(passphrase.expose().chars().filter(|c| c.is_ascii_digit()).count(), 1);
+
+            let wordlist_len = WORDLIST.lines().count() as f64;
+            let expected = 5.0 * wordlist_len.log2() + 10f64.log2() + 5f64.log2();
+            assert!((entropy - expected).abs() < 1e-9);
+        }
+
+        #[test]
+        fn search_patterns_escape_wildcards() {
+            let patterns = SearchPatterns::new(" 5%_off\\ ");
+
+            assert_eq!(patterns.exact, "5\\%\\_off\\\\");
+            assert_eq!(patterns.prefix, "5\\%\\_off\\\\%");
+            assert_eq!(patterns.substring, "%5\\%\\_off\\\\%");
+            assert_eq!(patterns.fuzzy, "%5%\\%%\\_%o%f%f%\\\\%");
+        }
+
+        #[test]
+        fn empty_search_matches_everything() {
+            assert_eq!(SearchPatterns::new("").fuzzy, "%%");
+        }
+
+        #[test]
+        fn login_delay_grows_until_lockout() {
+            let delays: Vec<i64> = (0..=LOCKOUT_LOGIN_ATTEMPTS + 1).map(login_delay).collect();
+
+            assert_eq!(&delays[..FREE_LOGIN_ATTEMPTS as usize], &[0, 0, 0]);
+            assert_eq!(&delays[FREE_LOGIN_ATTEMPTS as usize..LOCKOUT_LOGIN_ATTEMPTS as usize], &[1, 2, 4, 8, 16, 32, 64]);
+            assert!(delays[LOCKOUT_LOGIN_ATTEMPTS as usize..].iter().all(|&delay| delay == LOCKOUT_SECONDS));
+        }
+
+        #[test]
+        fn failed_logins_are_counted_per_username() {
+            let conn = Connection::open_in_memory().unwrap();
+            crate::migrations::migrations::migrate(&conn).unwrap();
+
+            for attempt in 1..=4 {
+                let result = record_failed_login(&conn, "someone", 1000).unwrap();
+                assert_eq!(result, AuthResult::Rejected { failed_attempts: attempt, retry_after: login_delay(attempt) });
+            }
+            assert_eq!(failed_logins(&conn, "someone").unwrap(), (4, 1000));
+            assert_eq!(failed_logins(&conn, "someone else").unwrap(), (0, 0));
+        }
+    }
+}
@@ -0,0 +1,82 @@
+/* secret module holds passwords in memory for as little time as
+   possible. A Secret wipes its text when dropped and never shows
+   it in debug output, so it only leaves through expose().     */
+
+pub mod secret {
+    use std::fmt;
+    use subtle::ConstantTimeEq;
+    use zeroize::Zeroize;
+
+    // a password (or other secret text), zeroed when it is dropped.
+    // There is deliberately no Display or Deref, so printing one always
+    // takes an explicit expose().
+    #[derive(Clone, Default)]
+    pub struct Secret(String);
+
+    impl Secret {
+        pub fn new(text: String) -> Self {
+            Secret(text)
+        }
+
+        // the text itself. Copies made from it are not wiped, so keep them short-lived.
+        pub fn expose(&self) -> &str {
+            &self.0
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.0.is_empty()
+        }
+    }
+
+    impl From<String> for Secret {
+        fn from(text: String) -> Self {
+            Secret(text)
+        }
+    }
+
+    impl From<&str> for Secret {
+        fn from(text: &str) -> Self {
+            Secret(text.to_string())
+        }
+    }
+
+    impl Drop for Secret {
+        fn drop(&mut self) {
+            self.0.zeroize();
+        }
+    }
+
+    impl fmt::Debug for Secret {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "Secret([REDACTED])")
+        }
+    }
+
+    // compared in constant time, so comparing a guess does not reveal how much of it was right
+    impl PartialEq for Secret {
+        fn eq(&self, other: &Self) -> bool {
+            bool::from(self.0.as_bytes().ct_eq(other.0.as_bytes()))
+        }
+    }
+
+    impl Eq for Secret {}
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn debug_output_is_redacted() {
+            let secret = Secret::from("hunter2");
+            assert_eq!(format!("{:?}", secret), "Secret([REDACTED])");
+            assert_eq!(format!("{:?}", Some(secret)), "Some(Secret([REDACTED]))");
+        }
+
+        #[test]
+        fn secrets_compare_by_contents() {
+            assert_eq!(Secret::from("hunter2"), Secret::from(String::from("hunter2")));
+            assert_ne!(Secret::from("hunter2"), Secret::from("hunter3"));
+            assert_ne!(Secret::from("hunter2"), Secret::from("hunter22"));
+        }
+    }
+}
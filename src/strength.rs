@@ -0,0 +1,605 @@
+/* strength module estimates how many guesses an attacker would need
+   to crack a password. Like Dropbox's zxcvbn, it looks for the patterns
+   attackers try first (common passwords and words, l33t substitutions,
+   keyboard walks, repeats, sequences, and dates), then finds the cheapest
+   way to build the password out of those patterns and random characters. */
+
+pub mod strength {
+    use crate::records::records::WORDLIST;
+    use std::collections::HashMap;
+    use zeroize::Zeroize;
+
+    // ordered from most to least common, so the line number is the rank
+    const COMMON_PASSWORDS: &str = include_str!("common_passwords.txt");
+
+    const BRUTEFORCE_CARDINALITY: f64 = 10.0; // guesses per random character
+    const MIN_WORD_LEN: usize = 3;
+    const MAX_WORD_LEN: usize = 24;
+    const MAX_L33T_VARIANTS: usize = 32; // keeps tokens like "1|1|1|" from exploding
+    const MIN_YEAR_SPACE: i32 = 20;
+    const KEYBOARD_STARTS: f64 = 47.0; // unshifted keys on the layout below
+    const KEYBOARD_DEGREE: f64 = 4.6; // average number of neighbors per key
+
+    // each row as (unshifted, shifted). Every row is shifted half a key to the
+    // right of the row above it, which decides which keys are neighbors.
+    const KEYBOARD_ROWS: [(&str, &str); 4] = [
+        ("`1234567890-=", "~!@#$%^&*()_+"),
+        ("qwertyuiop[]\\", "QWERTYUIOP{}|"),
+        ("asdfghjkl;'", "ASDFGHJKL:\""),
+        ("zxcvbnm,./", "ZXCVBNM<>?")
+    ];
+
+    const L33T_TABLE: [(char, &str); 15] = [
+        ('4', "a"), ('@', "a"), ('8', "b"), ('(', "c"), ('3', "e"),
+        ('6', "g"), ('1', "il"), ('!', "i"), ('|', "il"), ('0', "o"),
+        ('$', "s"), ('5', "s"), ('7', "t"), ('+', "t"), ('2', "z")
+    ];
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Pattern {
+        Dictionary { common: bool, user_input: bool, l33t: bool },
+        Keyboard { turns: usize },
+        Repeat,
+        Sequence,
+        Date
+    }
+
+    #[derive(Debug, Clone)]
+    struct Match {
+        start: usize,
+        end: usize, // exclusive
+        pattern: Pattern,
+        guesses: f64
+    }
+
+    pub struct Estimate {
+        pub guesses: f64,
+        pub score: u8, // 0 (very weak) to 4 (very strong)
+        pub warning: Option<String>,
+        pub suggestions: Vec<String>
+    }
+
+    // estimates the strength of password. user_inputs are other details that an
+    // attacker is likely to know (such as the account name and username).
+    // Every copy of the password (or part of it) made along the way is wiped.
+    pub fn estimate(password: &str, user_inputs: &[&str]) -> Estimate {
+        let mut chars: Vec<char> = Vec::with_capacity(password.chars().count());
+        chars.extend(password.chars());
+        let dictionary = build_dictionary(user_inputs);
+        let (guesses, sequence) = most_guessable(&chars, &dictionary);
+
+        let score = match guesses {
+            g if g < 1e3 + 5.0 => 0,
+            g if g < 1e6 + 5.0 => 1,
+            g if g < 1e8 + 5.0 => 2,
+            g if g < 1e10 + 5.0 => 3,
+            _ => 4
+        };
+        let (warning, suggestions) = feedback(score, &sequence, &chars);
+        chars.zeroize();
+
+        Estimate { guesses, score, warning, suggestions }
+    }
+
+    pub fn rating(score: u8) -> &'static str {
+        match score {
+            0 => "very weak",
+            1 => "weak",
+            2 => "fair",
+            3 => "strong",
+            _ => "very strong"
+        }
+    }
+
+    // maps each lowercase word to its rank and whether it is a common password
+    // or one of the user inputs. Lower ranks are guessed first.
+    fn build_dictionary(user_inputs: &[&str]) -> HashMap<String, (f64, Pattern)> {
+        let mut dictionary = HashMap::new();
+
+        // the wordlist is not ordered by frequency, so every word gets the same rank
+        let words: Vec<&str> = WORDLIST.lines().filter_map(|line| line.split_whitespace().last()).collect();
+        let word_rank = words.len() as f64;
+        for word in words {
+            dictionary.insert(word.to_lowercase(), (word_rank, Pattern::Dictionary { common: false, user_input: false, l33t: false }));
+        }
+        for (rank, password) in COMMON_PASSWORDS.lines().enumerate() {
+            dictionary.insert(password.to_lowercase(), ((rank + 1) as f64, Pattern::Dictionary { common: true, user_input: false, l33t: false }));
+        }
+        for (rank, input) in user_inputs.iter().enumerate() {
+            let input = input.trim().to_lowercase();
+            if input.chars().count() >= MIN_WORD_LEN {
+                dictionary.insert(input, ((rank + 1) as f64, Pattern::Dictionary { common: false, user_input: true, l33t: false }));
+            }
+        }
+        dictionary
+    }
+
+    // finds the sequence of non-overlapping matches (w/random characters filling
+    // the gaps) that needs the fewest guesses in total
+    fn most_guessable(chars: &[char], dictionary: &HashMap<String, (f64, Pattern)>) -> (f64, Vec<Match>) {
+        let n = chars.len();
+        let mut matches = dictionary_matches(chars, dictionary);
+        matches.extend(keyboard_matches(chars));
+        matches.extend(sequence_matches(chars));
+        matches.extend(repeat_matches(chars, dictionary));
+        matches.extend(date_matches(chars));
+
+        // best[j] is the fewest guesses for the first j characters, and
+        // previous[j] is the match that ends there (None for a random character)
+        let mut best: Vec<f64> = vec![1.0; n + 1];
+        let mut previous: Vec<Option<usize>> = vec![None; n + 1];
+        for j in 1..=n {
+            best[j] = best[j - 1] * BRUTEFORCE_CARDINALITY;
+            for (index, m) in matches.iter().enumerate().filter(|(_, m)| m.end == j) {
+                let candidate = best[m.start] * m.guesses;
+                if candidate < best[j] {
+                    best[j] = candidate;
+                    previous[j] = Some(index);
+                }
+            }
+        }
+
+        let mut sequence = Vec::new();
+        let mut j = n;
+        while j > 0 {
+            match previous[j] {
+                Some(index) => {
+                    sequence.push(matches[index].clone());
+                    j = matches[index].start;
+                }
+                None => j -= 1
+            }
+        }
+        sequence.reverse();
+
+        (best[n], sequence)
+    }
+
+    fn dictionary_matches(chars: &[char], dictionary: &HashMap<String, (f64, Pattern)>) -> Vec<Match> {
+        let mut matches = Vec::new();
+
+        for start in 0..chars.len() {
+            for end in (start + MIN_WORD_LEN)..=chars.len().min(start + MAX_WORD_LEN) {
+                let token = &chars[start..end];
+                let mut lower = token_string(token.iter().flat_map(|c| c.to_lowercase()));
+
+                if let Some((rank, pattern)) = dictionary.get(&lower) {
+                    matches.push(Match { start, end, pattern: pattern.clone(), guesses: rank * uppercase_variations(token) });
+                }
+
+                let mut unsubbed_variants = unl33t(&lower);
+                for unsubbed in &unsubbed_variants {
+                    if let Some((rank, Pattern::Dictionary { common, user_input, .. })) = dictionary.get(unsubbed) {
+                        let guesses = rank * uppercase_variations(token) * l33t_variations(&lower, unsubbed);
+                        matches.push(Match {
+                            start,
+                            end,
+                            pattern: Pattern::Dictionary { common: *common, user_input: *user_input, l33t: true },
+                            guesses
+                        });
+                    }
+                }
+                unsubbed_variants.zeroize();
+                lower.zeroize();
+            }
+        }
+        matches
+    }
+
+    // collects part of a password into a String that is the right size from the start,
+    // so growing it never leaves a copy behind. The caller wipes it when done.
+    fn token_string<T: Iterator<Item = char> + Clone>(token: T) -> String {
+        let mut string = String::with_capacity(token.clone().map(char::len_utf8).sum());
+        string.extend(token);
+        string
+    }
+
+    // every way the l33t characters in word can be read back as letters. Each l33t
+    // character and its letters are one byte, so no variant outgrows word.len().
+    fn unl33t(word: &str) -> Vec<String> {
+        let mut variants: Vec<String> = vec![String::with_capacity(word.len())];
+        let mut substituted = false;
+
+        for c in word.chars() {
+            match L33T_TABLE.iter().find(|(l33t, _)| *l33t == c) {
+                Some((_, letters)) => {
+                    substituted = true;
+                    let next: Vec<String> = variants.iter()
+                        .flat_map(|variant| letters.chars().map(move |letter| {
+                            let mut next = String::with_capacity(word.len());
+                            next.push_str(variant);
+                            next.push(letter);
+                            next
+                        }))
+                        .take(MAX_L33T_VARIANTS)
+                        .collect();
+                    variants.zeroize();
+                    variants = next;
+                }
+                None => {
+                    for variant in variants.iter_mut() {
+                        variant.push(c);
+                    }
+                }
+            }
+        }
+
+        if !substituted {
+            variants.zeroize();
+        }
+        variants
+    }
+
+    fn uppercase_variations(token: &[char]) -> f64 {
+        let upper = token.iter().filter(|c| c.is_uppercase()).count();
+        let lower = token.iter().filter(|c| c.is_lowercase()).count();
+
+        if upper == 0 {
+            return 1.0;
+        }
+        // all caps, or only the first or last letter capitalized, are tried early
+        let first_only = token.first().is_some_and(|c| c.is_uppercase()) && upper == 1;
+        let last_only = token.last().is_some_and(|c| c.is_uppercase()) && upper == 1;
+        if lower == 0 || first_only || last_only {
+            return 2.0;
+        }
+        (1..=upper.min(lower)).map(|i| n_choose_k(upper + lower, i)).sum()
+    }
+
+    fn l33t_variations(subbed: &str, unsubbed: &str) -> f64 {
+        let substitutions = subbed.chars().zip(unsubbed.chars()).filter(|(a, b)| a != b).count();
+        let plain = unsubbed.chars().count() - substitutions;
+
+        if plain == 0 {
+            return 2.0;
+        }
+        (1..=substitutions.min(plain)).map(|i| n_choose_k(substitutions + plain, i)).sum::<f64>().max(2.0)
+    }
+
+    // row, column, and whether shift is needed for a key
+    fn key_position(c: char) -> Option<(i32, i32, bool)> {
+        for (row, (unshifted, shifted)) in KEYBOARD_ROWS.iter().enumerate() {
+            if let Some(col) = unshifted.chars().position(|key| key == c) {
+                return Some((row as i32, col as i32, false));
+            }
+            if let Some(col) = shifted.chars().position(|key| key == c) {
+                return Some((row as i32, col as i32, true));
+            }
+        }
+        None
+    }
+
+    // direction from one key to a neighboring key, or None if they are not neighbors
+    fn key_direction(from: char, to: char) -> Option<(i32, i32)> {
+        let (from_row, from_col, _) = key_position(from)?;
+        let (to_row, to_col, _) = key_position(to)?;
+        let direction = (to_row - from_row, to_col - from_col);
+
+        match direction {
+            (0, -1) | (0, 1) | (-1, 0) | (-1, 1) | (1, -1) | (1, 0) => Some(direction),
+            _ => None
+        }
+    }
+
+    fn keyboard_matches(chars: &[char]) -> Vec<Match> {
+        let mut matches = Vec::new();
+
+        for start in 0..chars.len() {
+            let mut end = start + 1;
+            let mut turns = 0;
+            let mut last_direction = None;
+
+            while end < chars.len() {
+                match key_direction(chars[end - 1], chars[end]) {
+                    Some(direction) => {
+                        if last_direction != Some(direction) {
+                            turns += 1;
+                        }
+                        last_direction = Some(direction);
+                        end += 1;
+                    }
+                    None => break
+                }
+            }
+
+            if end - start >= 3 {
+                let token = &chars[start..end];
+                matches.push(Match { start, end, pattern: Pattern::Keyboard { turns }, guesses: keyboard_guesses(token, turns) });
+            }
+        }
+        matches
+    }
+
+    fn keyboard_guesses(token: &[char], turns: usize) -> f64 {
+        let length = token.len();
+        let mut guesses = 0.0;
+
+        // every walk up to this length w/up to this many turns
+        for i in 2..=length {
+            for j in 1..=turns.min(i - 1) {
+                guesses += n_choose_k(i - 1, j - 1) * KEYBOARD_STARTS * KEYBOARD_DEGREE.powi(j as i32);
+            }
+        }
+
+        let shifted = token.iter().filter(|c| key_position(**c).is_some_and(|(_, _, shift)| shift)).count();
+        let unshifted = length - shifted;
+        if shifted > 0 {
+            guesses *= if unshifted == 0 {
+                2.0
+            } else {
+                (1..=shifted.min(unshifted)).map(|i| n_choose_k(length, i)).sum()
+            };
+        }
+        guesses
+    }
+
+    // runs like "abcd", "9876", or "acegi" within a single character class
+    fn sequence_matches(chars: &[char]) -> Vec<Match> {
+        let class = |c: char| if c.is_ascii_lowercase() { 1 } else if c.is_ascii_uppercase() { 2 } else if c.is_ascii_digit() { 3 } else { 0 };
+        let mut matches = Vec::new();
+        let mut start = 0;
+
+        while start + 2 < chars.len() {
+            let delta = chars[start + 1] as i32 - chars[start] as i32;
+            let same_class = |i: usize| class(chars[i]) != 0 && class(chars[i]) == class(chars[start]);
+            let mut end = start + 1;
+
+            if (1..=2).contains(&delta.abs()) {
+                while end < chars.len() && same_class(end) && chars[end] as i32 - chars[end - 1] as i32 == delta {
+                    end += 1;
+                }
+            }
+
+            if end - start >= 3 {
+                let first = chars[start];
+                let mut base = if "aAzZ019".contains(first) { 4.0 } else if first.is_ascii_digit() { 10.0 } else { 26.0 };
+                if delta < 0 {
+                    base *= 2.0;
+                }
+                matches.push(Match { start, end, pattern: Pattern::Sequence, guesses: base * (end - start) as f64 });
+                start = end - 1;
+            } else {
+                start += 1;
+            }
+        }
+        matches
+    }
+
+    // the same character or block of characters over and over, like "aaaa" or "abcabc"
+    fn repeat_matches(chars: &[char], dictionary: &HashMap<String, (f64, Pattern)>) -> Vec<Match> {
+        let mut matches = Vec::new();
+        let mut start = 0;
+
+        // only the shortest repeating block at each position is used, and the
+        // search continues after it, so the number of matches stays small
+        while start < chars.len() {
+            let repeat = (1..=(chars.len() - start) / 2).find_map(|block| {
+                let unit = &chars[start..start + block];
+                let mut count = 1;
+                while start + (count + 1) * block <= chars.len()
+                    && &chars[start + count * block..start + (count + 1) * block] == unit {
+                    count += 1;
+                }
+                (count >= 2 && (block > 1 || count >= 3)).then_some((unit, count))
+            });
+
+            match repeat {
+                Some((unit, count)) => {
+                    let (unit_guesses, _) = most_guessable(unit, dictionary);
+                    let end = start + count * unit.len();
+                    matches.push(Match { start, end, pattern: Pattern::Repeat, guesses: unit_guesses * count as f64 });
+                    start = end;
+                }
+                None => start += 1
+            }
+        }
+        matches
+    }
+
+    fn date_matches(chars: &[char]) -> Vec<Match> {
+        let reference_year = current_year();
+        let year_space = |year: i32| (year - reference_year).abs().max(MIN_YEAR_SPACE) as f64;
+        let mut matches = Vec::new();
+
+        for start in 0..chars.len() {
+            for end in (start + 4)..=chars.len().min(start + 10) {
+                let mut token = token_string(chars[start..end].iter().copied());
+
+                let guesses = if token.chars().all(|c| c.is_ascii_digit()) {
+                    if token.len() == 4 {
+                        token.parse::<i32>().ok().filter(|year| (1900..=2099).contains(year)).map(year_space)
+                    } else {
+                        unseparated_date(&token).map(|year| 365.0 * year_space(year))
+                    }
+                } else {
+                    separated_date(&token).map(|year| 365.0 * year_space(year) * 4.0)
+                };
+
+                token.zeroize();
+                if let Some(guesses) = guesses {
+                    matches.push(Match { start, end, pattern: Pattern::Date, guesses });
+                }
+            }
+        }
+        matches
+    }
+
+    // returns the year of a date written w/o separators, like "25121990" or "901225"
+    fn unseparated_date(token: &str) -> Option<i32> {
+        let splits: &[(usize, usize)] = match token.len() {
+            6 => &[(2, 4)],
+            8 => &[(2, 4), (4, 6)],
+            _ => return None
+        };
+        splits.iter().find_map(|&(first, second)| {
+            date_year(&[&token[..first], &token[first..second], &token[second..]])
+        })
+    }
+
+    // returns the year of a date written w/one kind of separator, like "12/25/90"
+    fn separated_date(token: &str) -> Option<i32> {
+        let separator = token.chars().find(|c| !c.is_ascii_digit())?;
+        if !"/-._ ".contains(separator) {
+            return None;
+        }
+        let parts: Vec<&str> = token.split(separator).collect();
+        if parts.len() != 3 || parts.iter().any(|part| part.is_empty() || part.len() > 4 || !part.chars().all(|c| c.is_ascii_digit())) {
+            return None;
+        }
+        date_year(&parts)
+    }
+
+    // tries day-month-year, month-day-year, and year-month-day orders
+    fn date_year(parts: &[&str]) -> Option<i32> {
+        let orders = [(0, 1, 2), (1, 0, 2), (2, 1, 0)];
+        orders.iter().find_map(|&(day, month, year)| {
+            let day: u32 = parts[day].parse().ok()?;
+            let month: u32 = parts[month].parse().ok()?;
+            let year = match parts[year].len() {
+                2 => {
+                    let short: i32 = parts[year].parse().ok()?;
+                    if short > 50 { 1900 + short } else { 2000 + short }
+                }
+                4 => parts[year].parse().ok().filter(|year| (1900..=2099).contains(year))?,
+                _ => return None
+            };
+            ((1..=31).contains(&day) && (1..=12).contains(&month)).then_some(year)
+        })
+    }
+
+    fn current_year() -> i32 {
+        let seconds = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        1970 + (seconds / 31_556_952) as i32 // average seconds in a Gregorian year
+    }
+
+    fn n_choose_k(n: usize, k: usize) -> f64 {
+        if k > n {
+            return 0.0;
+        }
+        (1..=k).fold(1.0, |result, i| result * (n + 1 - i) as f64 / i as f64)
+    }
+
+    // explains the weakest part of the password. Strong passwords get no feedback.
+    fn feedback(score: u8, sequence: &[Match], chars: &[char]) -> (Option<String>, Vec<String>) {
+        if score > 2 {
+            return (None, Vec::new());
+        }
+
+        let mut suggestions = vec![String::from("Add another word or two. Uncommon words are better.")];
+        let longest = match sequence.iter().max_by_key(|m| m.end - m.start) {
+            Some(m) => m,
+            None => return (None, suggestions) // short, but w/o any known pattern
+        };
+
+        let warning = match &longest.pattern {
+            Pattern::Dictionary { common, user_input, l33t } => {
+                let token = &chars[longest.start..longest.end];
+                if token.first().is_some_and(|c| c.is_uppercase()) {
+                    suggestions.push(String::from("Capitalization doesn't help very much."));
+                }
+                if *l33t {
+                    suggestions.push(String::from("Predictable substitutions like '@' instead of 'a' don't help very much."));
+                }
+                if *user_input {
+                    "Passwords that contain the account name or username are easy to guess."
+                } else if *common {
+                    "This is similar to a commonly used password."
+                } else if sequence.len() == 1 && longest.start == 0 && longest.end == chars.len() {
+                    "A word by itself is easy to guess."
+                } else {
+                    "Common words are easy to guess."
+                }
+            }
+            Pattern::Keyboard { turns } => {
+                suggestions.push(String::from("Use a longer keyboard pattern with more turns."));
+                if *turns == 1 { "Straight rows of keys are easy to guess." } else { "Short keyboard patterns are easy to guess." }
+            }
+            Pattern::Repeat => {
+                suggestions.push(String::from("Avoid repeated words and characters."));
+                "Repeats like \"abcabc\" are only slightly harder to guess than \"abc\"."
+            }
+            Pattern::Sequence => {
+                suggestions.push(String::from("Avoid sequences."));
+                "Sequences like abc or 6543 are easy to guess."
+            }
+            Pattern::Date => {
+                suggestions.push(String::from("Avoid dates and years that are associated with you."));
+                "Dates are often easy to guess."
+            }
+        };
+
+        (Some(String::from(warning)), suggestions)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn patterns(password: &str) -> Vec<Pattern> {
+            let chars: Vec<char> = password.chars().collect();
+            most_guessable(&chars, &build_dictionary(&[])).1.into_iter().map(|m| m.pattern).collect()
+        }
+
+        #[test]
+        fn common_passwords_are_very_weak() {
+            for password in ["password", "123456", "qwerty", "letmein"] {
+                assert_eq!(estimate(password, &[]).score, 0, "{}", password);
+            }
+        }
+
+        #[test]
+        fn l33t_and_capitals_are_still_found() {
+            let result = patterns("P@ssw0rd");
+            assert_eq!(result, vec![Pattern::Dictionary { common: true, user_input: false, l33t: true }]);
+            assert!(estimate("P@ssw0rd", &[]).score <= 1);
+        }
+
+        #[test]
+        fn finds_keyboard_walks() {
+            assert_eq!(patterns("zxcvfr"), vec![Pattern::Keyboard { turns: 2 }]);
+            assert_eq!(patterns("sdfghjk"), vec![Pattern::Keyboard { turns: 1 }]);
+        }
+
+        #[test]
+        fn finds_repeats_and_sequences() {
+            assert_eq!(patterns("zzzzzzzz"), vec![Pattern::Repeat]);
+            assert_eq!(patterns("mnopqrs"), vec![Pattern::Sequence]);
+        }
+
+        #[test]
+        fn finds_dates() {
+            assert_eq!(patterns("12/25/1990"), vec![Pattern::Date]);
+            assert_eq!(patterns("19901225"), vec![Pattern::Date]);
+        }
+
+        #[test]
+        fn user_inputs_count_as_dictionary_words() {
+            let result = estimate("spotifyrocks", &["Spotify"]);
+            assert_eq!(result.warning.as_deref(), Some("Passwords that contain the account name or username are easy to guess."));
+        }
+
+        #[test]
+        fn random_passwords_are_strong() {
+            let result = estimate("vK9#qT2$mW7!xR", &[]);
+            assert_eq!(result.score, 4);
+            assert!(result.warning.is_none() && result.suggestions.is_empty());
+        }
+
+        #[test]
+        fn long_l33t_tokens_finish_quickly() {
+            let password = "1|".repeat(100);
+            assert_eq!(estimate(&password, &[]).score, 1);
+        }
+
+        #[test]
+        fn longer_passphrases_score_higher() {
+            let short = estimate("crater", &[]);
+            let long = estimate("crater-olive-magnet-frozen", &[]);
+            assert!(long.guesses > short.guesses);
+            assert!(long.score >= 3);
+        }
+    }
+}
@@ -0,0 +1,132 @@
+/* terminal module reads secrets (such as master passwords) from
+   the user w/o showing them on the screen as they are typed.   */
+
+pub mod terminal {
+    use std::io::{self, BufRead, Write};
+    use zeroize::Zeroize;
+    use crate::secret::secret::Secret;
+
+    // reads one line of secret input. When stdin is a terminal, echo is turned
+    // off while the user types. Piped input (like in scripts) is read as-is.
+    // Only the line ending is removed, since spaces may be part of a password.
+    pub fn read_secret() -> io::Result<Secret> {
+        io::stdout().flush()?;
+        read_secret_from(&mut io::stdin().lock(), stdin_is_tty())
+    }
+
+    // same as read_secret, but reads from any input (such as a script in tests).
+    // hide_echo should only be set when input comes from the stdin terminal.
+    pub fn read_secret_from<R: BufRead>(input: &mut R, hide_echo: bool) -> io::Result<Secret> {
+        let mut buffer = Vec::with_capacity(LINE_CAPACITY);
+        let result = if hide_echo {
+            read_line_without_echo(input, &mut buffer)
+        } else {
+            read_line_wiped(input, &mut buffer)
+        };
+        let secret = std::str::from_utf8(&buffer)
+            .map(|line| Secret::from(line.trim_end_matches(['\r', '\n'])))
+            .map_err(|_| invalid_utf8());
+
+        // the copy above is all that is kept, so the read buffer is wiped
+        buffer.zeroize();
+        result.and(secret)
+    }
+
+    // room for any line that is typed by hand, so reading one never has to grow the buffer
+    pub const LINE_CAPACITY: usize = 1024;
+
+    // same as BufRead::read_until w/a newline, but when line has to grow, the buffer it
+    // outgrew is wiped before it is given back, so no partial copy of a password is left
+    // behind. Whatever input buffered itself (like std's Stdin buffer) is out of reach.
+    pub fn read_line_wiped<R: BufRead>(input: &mut R, line: &mut Vec<u8>) -> io::Result<usize> {
+        let start = line.len();
+        loop {
+            let available = match input.fill_buf() {
+                Ok(available) => available,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e)
+            };
+            let (used, ended) = match available.iter().position(|&byte| byte == b'\n') {
+                Some(newline) => (newline + 1, true),
+                None => (available.len(), available.is_empty())
+            };
+
+            if line.len() + used > line.capacity() {
+                let mut grown = Vec::with_capacity((line.len() + used).max(line.capacity() * 2));
+                grown.extend_from_slice(line);
+                line.zeroize();
+                *line = grown;
+            }
+            line.extend_from_slice(&available[..used]);
+            input.consume(used);
+
+            if ended {
+                return Ok(line.len() - start);
+            }
+        }
+    }
+
+    pub fn invalid_utf8() -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, "stream did not contain valid UTF-8")
+    }
+
+    #[cfg(unix)]
+    pub fn stdin_is_tty() -> bool {
+        // SAFETY: isatty only inspects the file descriptor
+        unsafe { libc::isatty(libc::STDIN_FILENO) == 1 }
+    }
+
+    #[cfg(not(unix))]
+    pub fn stdin_is_tty() -> bool {
+        false // echo can only be turned off on unix terminals
+    }
+
+    // puts the terminal settings back when dropped, even if reading fails
+    #[cfg(unix)]
+    pub struct EchoGuard {
+        original: libc::termios
+    }
+
+    #[cfg(not(unix))]
+    pub struct EchoGuard;
+
+    #[cfg(unix)]
+    impl Drop for EchoGuard {
+        fn drop(&mut self) {
+            // SAFETY: original was filled in by tcgetattr for the same descriptor
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original);
+            }
+        }
+    }
+
+    // turns off echo on the stdin terminal until the guard is dropped. Whatever is
+    // typed in the meantime is hidden, no matter which thread reads it.
+    #[cfg(unix)]
+    pub fn hide_echo() -> io::Result<EchoGuard> {
+        // SAFETY: termios is plain data, and tcgetattr overwrites all of it
+        let mut settings: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut settings) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let guard = EchoGuard { original: settings };
+
+        // the newline is still echoed so the next output starts on its own line
+        settings.c_lflag &= !libc::ECHO;
+        settings.c_lflag |= libc::ECHONL;
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &settings) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(guard)
+    }
+
+    #[cfg(not(unix))]
+    pub fn hide_echo() -> io::Result<EchoGuard> {
+        Ok(EchoGuard)
+    }
+
+    fn read_line_without_echo<R: BufRead>(input: &mut R, buffer: &mut Vec<u8>) -> io::Result<usize> {
+        let _guard = hide_echo()?;
+        read_line_wiped(input, buffer)
+    }
+}
@@ -0,0 +1,412 @@
+// drives whole menu sessions from scripted input against a temporary vault
+// and checks the transcript the menu writes
+
+use password_manager::clipboard::clipboard::{Clipboard, ClipboardCopier};
+use password_manager::menu::menu::{Answer, Input, Interrupt, LineReader, Menu};
+use password_manager::records::records::{self as records, Login, Settings, User};
+use std::collections::VecDeque;
+use std::io::{self, Cursor};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const USERNAME: &str = "tester@example.com";
+const MASTER_PASSWORD: &str = "Correct-Horse-42";
+
+// a new vault file in its own directory, which is removed when the test ends
+struct TempVault {
+    dir: PathBuf
+}
+
+impl TempVault {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("password_manager_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let vault = TempVault { dir };
+        records::create_vault(&vault.path()).expect("could not create the vault");
+        vault
+    }
+
+    fn path(&self) -> PathBuf {
+        self.dir.join("vault.db")
+    }
+}
+
+impl Drop for TempVault {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+// runs the menu w/one line of input per entry in script and
+// returns the exit code along w/everything the menu wrote
+fn run_session(vault: &TempVault, script: &[&str]) -> (i32, String) {
+    run_session_with(vault, script, |_| {})
+}
+
+// same as run_session, but lets the menu be set up first (such as w/a clipboard)
+fn run_session_with(vault: &TempVault, script: &[&str], setup: impl FnOnce(&mut Menu<LineReader, Vec<u8>>)) -> (i32, String) {
+    let mut input = script.join("\n");
+    input.push('\n');
+
+    let mut menu = Menu::new(LineReader::new(Cursor::new(input.into_bytes())), Vec::new(), vault.path());
+    setup(&mut menu);
+    let code = menu.run();
+    (code, String::from_utf8(menu.into_output()).expect("the transcript is not UTF-8"))
+}
+
+// scripted input where None stands for a user who walked away. The prompt waiting
+// at that point runs out of time right away (if the session can lock at all).
+struct IdleInput {
+    lines: VecDeque<Option<&'static str>>,
+    // how long each prompt that ran out of time was given
+    timeouts: Arc<Mutex<Vec<Duration>>>
+}
+
+impl Input for IdleInput {
+    fn next_line(&mut self, timeout: Option<Duration>) -> Answer<String> {
+        loop {
+            match (self.lines.pop_front(), timeout) {
+                (None, _) => return Err(Interrupt::EndOfInput),
+                (Some(Some(line)), _) => return Ok(line.to_string()),
+                (Some(None), Some(timeout)) => {
+                    self.timeouts.lock().unwrap().push(timeout);
+                    return Err(Interrupt::IdleTimeout);
+                }
+                // a prompt that never times out just waits for the user to come back
+                (Some(None), None) => {}
+            }
+        }
+    }
+}
+
+// runs the menu on an IdleInput script, and returns the transcript
+// along w/how long each prompt that ran out of time was given
+fn run_idle_session(vault: &TempVault, script: &[Option<&'static str>]) -> (String, Vec<Duration>) {
+    let timeouts = Arc::new(Mutex::new(Vec::new()));
+    let input = IdleInput { lines: script.iter().copied().collect(), timeouts: Arc::clone(&timeouts) };
+
+    let mut menu = Menu::new(input, Vec::new(), vault.path());
+    menu.run();
+    let transcript = String::from_utf8(menu.into_output()).unwrap();
+    let timeouts = timeouts.lock().unwrap().clone();
+    (transcript, timeouts)
+}
+
+// sets how long the test user's session can be left alone, w/o going through the menu
+fn set_auto_lock(vault: &TempVault, seconds: u32) {
+    let conn = records::open_vault(&vault.path()).expect("could not open the vault");
+    let user = User::log_in(&conn, USERNAME, MASTER_PASSWORD).expect("the test user could not log in");
+    user.set_auto_lock(&conn, seconds).expect("the auto-lock setting was not saved");
+}
+
+fn register<'a>() -> Vec<&'a str> {
+    vec!["c", USERNAME, MASTER_PASSWORD, MASTER_PASSWORD, ""]
+}
+
+#[test]
+fn full_session_adds_and_lists_an_entry() {
+    let vault = TempVault::new("full_session");
+    let mut script = register();
+    script.extend([
+        "y", USERNAME, MASTER_PASSWORD,
+        "1", "n", "GitHub", "octocat", "Tr0ub4dor&3-staple-battery", "y", "", "n",
+        "2", "reveal 1", "",
+        "8",
+        "n"
+    ]);
+
+    let (code, transcript) = run_session(&vault, &script);
+
+    assert_eq!(code, 0);
+    assert!(transcript.contains("Your account was created!"), "{}", transcript);
+    assert!(transcript.contains("You are logged in!"), "{}", transcript);
+    assert!(transcript.contains("New account successfully added!"), "{}", transcript);
+    assert!(transcript.contains("Account 1: GitHub\nUsername: octocat\nPassword: ********\n"), "{}", transcript);
+    assert!(transcript.contains("Account 1: GitHub\nUsername: octocat\nPassword: Tr0ub4dor&3-staple-battery"), "{}", transcript);
+    assert!(transcript.contains("Logout successful"), "{}", transcript);
+    assert!(transcript.trim_end().ends_with("Goodbye!"), "{}", transcript);
+}
+
+#[test]
+fn entries_are_kept_between_sessions() {
+    let vault = TempVault::new("between_sessions");
+    let mut script = register();
+    script.extend(["y", USERNAME, MASTER_PASSWORD, "1", "n", "Bank", "me", "n0t-A-weak-password!", "y", "", "n", "8", "n"]);
+    run_session(&vault, &script);
+
+    let (_, transcript) = run_session(&vault, &["y", USERNAME, MASTER_PASSWORD, "2", "r 1", "", "8", "n"]);
+
+    assert!(transcript.contains("Account 1: Bank\nUsername: me\nPassword: n0t-A-weak-password!"), "{}", transcript);
+}
+
+#[test]
+fn search_lists_matching_entries() {
+    let vault = TempVault::new("search");
+    let mut script = register();
+    script.extend(["y", USERNAME, MASTER_PASSWORD,
+        "1", "n", "GitHub", "octocat", "Tr0ub4dor&3-staple-battery", "y", "", "y",
+        "n", "Bank", "me", "n0t-A-weak-password!", "y", "", "n",
+        "3", "hub", "",
+        "3", "zzz",
+        "8", "n"]);
+
+    let (_, transcript) = run_session(&vault, &script);
+
+    assert!(transcript.contains("1 entry matches \"hub\":\n\nAccount 1: GitHub"), "{}", transcript);
+    assert!(!transcript.contains("Account 1: Bank"), "{}", transcript);
+    assert!(transcript.contains("No entries match \"zzz\"."), "{}", transcript);
+}
+
+#[test]
+fn listings_mask_passwords_until_revealed() {
+    let vault = TempVault::new("masked");
+    let mut script = register();
+    script.extend(["y", USERNAME, MASTER_PASSWORD,
+        "1", "n", "GitHub", "octocat", "Tr0ub4dor&3-staple-battery", "y", "", "y",
+        "n", "Bank", "me", "n0t-A-weak-password!", "y", "", "n",
+        "2", "reveal 2", "reveal 3", "show 1", "",
+        "8", "n"]);
+
+    let (_, transcript) = run_session(&vault, &script);
+
+    assert!(!transcript.contains("Tr0ub4dor&3-staple-battery"), "{}", transcript);
+    assert_eq!(transcript.matches("n0t-A-weak-password!").count(), 1, "{}", transcript);
+    assert!(transcript.contains("Account 2: Bank\nUsername: me\nPassword: n0t-A-weak-password!"), "{}", transcript);
+    assert!(transcript.contains("ERROR: There is no account 3."), "{}", transcript);
+    assert!(transcript.contains("Please enter reveal or copy followed by an account number."), "{}", transcript);
+}
+
+#[test]
+fn custom_fields_are_added_edited_and_shown() {
+    let vault = TempVault::new("custom_fields");
+    let mut script = register();
+    script.extend(["y", USERNAME, MASTER_PASSWORD,
+        "1", "n", "GitHub", "octocat", "Tr0ub4dor&3-staple-battery", "y",
+        "a", "url", "", "not a url", "github.com/login",
+        "a", "pin", "h", "Recovery codes", "1234-5678",
+        "a", "text", "", "spare key is in the drawer",
+        "", "n",
+        "2", "reveal 1", "",
+        "4", "1", "", "", "n", "remove 3", "remove 9", "",
+        "2", "",
+        "8", "n"]);
+
+    let (_, transcript) = run_session(&vault, &script);
+
+    assert!(transcript.contains("ERROR: \"not a url\" is not a valid URL."), "{}", transcript);
+    assert!(transcript.contains("Please enter text, hidden, url, or email."), "{}", transcript);
+    assert!(transcript.contains("Custom fields:\n1. URL (url): https://github.com/login\n2. Recovery codes (hidden): ********\n"), "{}", transcript);
+    // hidden fields are masked in listings like passwords are, until revealed
+    assert!(transcript.contains("Password: ********\nURL: https://github.com/login\nRecovery codes: ********\nNotes: spare key is in the drawer\n"), "{}", transcript);
+    assert!(transcript.contains("Password: Tr0ub4dor&3-staple-battery\nURL: https://github.com/login\nRecovery codes: 1234-5678\nNotes: spare key is in the drawer\n"), "{}", transcript);
+    assert!(transcript.contains("ERROR: There is no field 9. Please enter a number from 1 - 2."), "{}", transcript);
+    assert!(transcript.contains("Account successfully updated!"), "{}", transcript);
+    // the notes were removed, so the last listing ends w/the recovery codes
+    assert!(transcript.contains("Recovery codes: ********\n\nEnter reveal N"), "{}", transcript);
+    assert_eq!(transcript.matches("1234-5678").count(), 1, "{}", transcript);
+}
+
+// remembers everything done to it, in order
+#[derive(Clone, Default)]
+struct RecordingClipboard {
+    history: Arc<Mutex<Vec<String>>>
+}
+
+impl Clipboard for RecordingClipboard {
+    fn name(&self) -> String {
+        String::from("the test clipboard")
+    }
+
+    fn set(&self, text: &str) -> io::Result<()> {
+        self.history.lock().unwrap().push(format!("set {}", text));
+        Ok(())
+    }
+
+    fn clear(&self) -> io::Result<()> {
+        self.history.lock().unwrap().push(String::from("clear"));
+        Ok(())
+    }
+}
+
+#[test]
+fn copied_passwords_are_cleared_at_logout() {
+    let vault = TempVault::new("copy");
+    let clipboard = RecordingClipboard::default();
+    let mut script = register();
+    script.extend(["y", USERNAME, MASTER_PASSWORD,
+        "1", "n", "GitHub", "octocat", "Tr0ub4dor&3-staple-battery", "y", "", "n",
+        "2", "copy 1", "copy 2", "", "8", "n"]);
+
+    let recorder = clipboard.clone();
+    let (_, transcript) = run_session_with(&vault, &script, |menu| {
+        menu.set_clipboard(ClipboardCopier::new(Box::new(recorder), Duration::from_secs(60)));
+    });
+
+    assert!(transcript.contains("The password for GitHub was copied w/the test clipboard. It will be cleared in 60 seconds."), "{}", transcript);
+    assert!(transcript.contains("ERROR: There is no account 2."), "{}", transcript);
+    assert!(!transcript.contains("Tr0ub4dor&3-staple-battery"), "{}", transcript);
+    // logging out clears the clipboard long before the timeout would have
+    assert_eq!(*clipboard.history.lock().unwrap(), ["set Tr0ub4dor&3-staple-battery", "clear"]);
+}
+
+#[test]
+fn idle_sessions_lock_until_the_master_password_is_entered() {
+    let vault = TempVault::new("auto_lock");
+    let mut script = register();
+    script.extend(["y", USERNAME, MASTER_PASSWORD,
+        "1", "n", "GitHub", "octocat", "Tr0ub4dor&3-staple-battery", "y", "", "n",
+        "8", "n"]);
+    run_session(&vault, &script);
+    set_auto_lock(&vault, 1);
+
+    // the session locks while the listing waits for an answer
+    let (transcript, timeouts) = run_idle_session(&vault, &[
+        Some("y"), Some(USERNAME), Some(MASTER_PASSWORD),
+        Some("2"), None, Some(MASTER_PASSWORD),
+        Some("2"), Some("reveal 1"), Some(""),
+        None, Some("wrong password"),
+        Some("n")
+    ]);
+
+    assert_eq!(transcript.matches("Your session was locked after 1 second of inactivity.").count(), 2, "{}", transcript);
+    assert!(transcript.contains("Your session is unlocked."), "{}", transcript);
+    // only the reveal made after unlocking shows the password
+    assert_eq!(transcript.matches("Tr0ub4dor&3-staple-battery").count(), 1, "{}", transcript);
+    assert!(transcript.contains("ERROR: The master password is incorrect.\n\nLogout successful"), "{}", transcript);
+    assert_eq!(timeouts, [Duration::from_secs(1); 2]);
+}
+
+// a sub-menu left waiting locks the session too, and does nothing once it is answered
+#[test]
+fn idle_sub_menus_lock_before_acting() {
+    let vault = TempVault::new("auto_lock_sub_menu");
+    let mut script = register();
+    script.extend(["y", USERNAME, MASTER_PASSWORD,
+        "1", "n", "GitHub", "octocat", "Tr0ub4dor&3-staple-battery", "y", "", "n",
+        "8", "n"]);
+    run_session(&vault, &script);
+    set_auto_lock(&vault, 1);
+
+    let (transcript, _) = run_idle_session(&vault, &[
+        Some("y"), Some(USERNAME), Some(MASTER_PASSWORD),
+        Some("5"), Some("1"), None, Some("y"),
+        Some("y"), Some(USERNAME), Some(MASTER_PASSWORD),
+        Some("2"), Some(""), Some("8"),
+        Some("n")
+    ]);
+
+    assert!(transcript.contains("Are you sure you want to delete it? Enter (y/n): \nYour session was locked after 1 second of inactivity."), "{}", transcript);
+    // the late "y" was taken as a (wrong) master password instead of deleting the entry
+    assert!(transcript.contains("ERROR: The master password is incorrect."), "{}", transcript);
+    assert!(!transcript.contains("Account moved to the trash."), "{}", transcript);
+    assert!(transcript.contains("Account 1: GitHub"), "{}", transcript);
+}
+
+#[test]
+fn auto_lock_can_be_changed_per_client() {
+    let vault = TempVault::new("auto_lock_settings");
+    let mut script = register();
+    script.extend(["y", USERNAME, MASTER_PASSWORD, "7", "abc", "10", "8", "n"]);
+    let (_, first) = run_session(&vault, &script);
+
+    let (_, second) = run_session(&vault, &["y", USERNAME, MASTER_PASSWORD, "7", "0", "7", "", "8", "n"]);
+
+    assert!(first.contains("Your session locks after 5 minutes of inactivity."), "{}", first);
+    assert!(first.contains("ERROR: Please enter a whole number of minutes."), "{}", first);
+    assert!(first.contains("Your auto-lock setting was saved."), "{}", first);
+    // the setting belongs to the client, so it is still there after logging in again
+    assert!(second.contains("Your session locks after 10 minutes of inactivity."), "{}", second);
+    assert!(second.contains("Your session never locks on its own."), "{}", second);
+}
+
+#[test]
+fn failed_login_returns_to_main_menu() {
+    let vault = TempVault::new("failed_login");
+    let mut script = register();
+    script.extend(["y", USERNAME, "wrong password", "y", "nobody", "wrong password", "n"]);
+
+    let (code, transcript) = run_session(&vault, &script);
+
+    assert_eq!(code, 0);
+    assert_eq!(transcript.matches("The login attempt failed.").count(), 2, "{}", transcript);
+    assert!(!transcript.contains("You are logged in!"));
+    // quitting once is enough, however many logins failed before it
+    assert_eq!(transcript.matches("Goodbye!").count(), 1);
+}
+
+#[test]
+fn end_of_input_exits_cleanly() {
+    let vault = TempVault::new("end_of_input");
+    let mut script = register();
+    script.extend(["y", USERNAME, MASTER_PASSWORD]);
+
+    let (code, transcript) = run_session(&vault, &script);
+
+    assert_eq!(code, 0);
+    assert!(transcript.contains("Logout successful"), "{}", transcript);
+    assert!(transcript.trim_end().ends_with("Goodbye!"), "{}", transcript);
+}
+
+// input can end at any prompt, even one in the middle of adding or deleting an entry
+#[test]
+fn end_of_input_inside_a_sub_menu_exits_cleanly() {
+    let vault = TempVault::new("end_of_input_sub_menu");
+    let mut script = register();
+    script.extend(["y", USERNAME, MASTER_PASSWORD, "1", "n", "GitHub", "octocat", "Tr0ub4dor&3-staple-battery", "y", "", "n", "8", "n"]);
+    run_session(&vault, &script);
+
+    let login = ["y", USERNAME, MASTER_PASSWORD];
+    let endings: [&[&str]; 4] = [
+        &["1", "n", "Bank", "me"],           // at a hidden password prompt
+        &["1", "n", "Bank", "me", "hunter2"], // at the prompt after it
+        &["5", "1"],                          // at the delete confirmation
+        &["2", "copy 1", "", "4"]             // after copying a password
+    ];
+    for ending in endings {
+        let clipboard = RecordingClipboard::default();
+        let recorder = clipboard.clone();
+        let script: Vec<&str> = login.iter().chain(ending).copied().collect();
+        let (code, transcript) = run_session_with(&vault, &script, |menu| {
+            menu.set_clipboard(ClipboardCopier::new(Box::new(recorder), Duration::from_secs(60)));
+        });
+
+        assert_eq!(code, 0, "{}", transcript);
+        assert!(transcript.trim_end().ends_with("Logout successful\n\nGoodbye!"), "{}", transcript);
+        assert!(!transcript.contains("Account moved to the trash."), "{}", transcript);
+        // a copied password is still cleared on the way out
+        let history = clipboard.history.lock().unwrap().clone();
+        assert!(history.is_empty() || history == ["set Tr0ub4dor&3-staple-battery", "clear"], "{:?}", history);
+    }
+}
+
+// a mistyped vault name must not turn into a new, empty vault
+#[test]
+fn missing_vaults_are_only_created_when_asked() {
+    let vault = TempVault::new("missing_vault");
+    let (typo, other) = (vault.dir.join("typo.db"), vault.dir.join("other.db"));
+    let (typo_name, other_name) = (typo.to_str().unwrap(), other.to_str().unwrap());
+
+    let (code, transcript) = run_session(&vault, &["v", typo_name, "n", "v", other_name, "y", "n"]);
+
+    assert_eq!(code, 0);
+    assert!(!typo.exists());
+    assert!(transcript.contains(&format!("ERROR: No vault at {} was found.", typo_name)), "{}", transcript);
+    assert!(transcript.contains(&format!("Still using {}", vault.path().display())), "{}", transcript);
+    assert!(other.exists());
+    assert!(transcript.contains(&format!("Created {}\nSwitched to {}", other_name, other_name)), "{}", transcript);
+
+    // starting on a vault that does not exist only opens it if the user creates it
+    let mut menu = Menu::new(LineReader::new(Cursor::new(b"n\n".to_vec())), Vec::new(), typo.clone());
+    assert_eq!(menu.run(), 3);
+    assert!(!typo.exists());
+}
+
+#[test]
+fn weak_master_password_is_refused() {
+    let vault = TempVault::new("weak_password");
+    let (_, transcript) = run_session(&vault, &["c", USERNAME, "password", "", "n"]);
+
+    assert!(transcript.contains("That master password is too weak."), "{}", transcript);
+    assert!(!transcript.contains("Your account was created!"));
+}
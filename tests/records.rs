@@ -0,0 +1,352 @@
+// exercises the records functions against an in-memory vault w/the
+// current schema, including that clients can never see each other's entries
+
+use password_manager::crypto::crypto::{CryptoError, KdfParams, VaultKey};
+use password_manager::error::error::Error;
+use password_manager::fields::fields::{CustomField, FieldKind};
+use password_manager::migrations::migrations::migrate;
+use password_manager::records::records::{self as records, AccountInfo, AuthResult, ClientId, Login, Register, Retrieve, Transfer, User};
+use password_manager::secret::secret::Secret;
+use rusqlite::Connection;
+
+const ALICE: (&str, &str) = ("alice@example.com", "Alice-Master-Pass-1");
+const BOB: (&str, &str) = ("bob@example.com", "Bob-Master-Pass-22");
+
+fn vault() -> Connection {
+    let conn = Connection::open_in_memory().expect("could not open an in-memory db");
+    migrate(&conn).expect("could not create the schema");
+    conn
+}
+
+// registers both test clients
+fn seeded_vault() -> Connection {
+    let conn = vault();
+    for (username, password) in [ALICE, BOB] {
+        User::register_user(&conn, username, password).expect("could not register a client");
+    }
+    conn
+}
+
+fn log_in(conn: &Connection, (username, password): (&str, &str)) -> User {
+    User::log_in(conn, username, password).unwrap_or_else(|e| panic!("{} could not log in: {}", username, e))
+}
+
+fn entry(account: &str, username: &str, password: &str) -> AccountInfo {
+    AccountInfo {
+        account: account.to_string(),
+        username: username.to_string(),
+        password: Secret::from(password),
+        accountId: None,
+        fields: Vec::new()
+    }
+}
+
+fn add(conn: &Connection, user: &User, entry: AccountInfo) {
+    AccountInfo::add_account(conn, entry, &user.get_id(), user.get_vault_key()).expect("the entry was not added");
+}
+
+#[test]
+fn lookup_user_accepts_the_right_password() {
+    let conn = seeded_vault();
+
+    assert!(matches!(User::lookup_user(&conn, ALICE.0, ALICE.1).unwrap(), AuthResult::Authenticated(_)));
+}
+
+#[test]
+fn lookup_user_rejects_wrong_password_and_unknown_user() {
+    let conn = seeded_vault();
+
+    assert_eq!(User::lookup_user(&conn, ALICE.0, BOB.1).unwrap(),
+        AuthResult::Rejected { failed_attempts: 1, retry_after: 0 });
+    assert_eq!(User::lookup_user(&conn, "nobody@example.com", ALICE.1).unwrap(),
+        AuthResult::Rejected { failed_attempts: 1, retry_after: 0 });
+}
+
+#[test]
+fn duplicate_usernames_are_refused() {
+    let conn = seeded_vault();
+
+    assert!(User::username_taken(&conn, ALICE.0).unwrap());
+    assert!(matches!(User::register_user(&conn, ALICE.0, "Another-Pass-333"), Err(Error::Duplicate(_))));
+}
+
+#[test]
+fn added_accounts_are_read_back() {
+    let conn = seeded_vault();
+    let alice = log_in(&conn, ALICE);
+    add(&conn, &alice, entry("GitHub", "alice", "gh-secret"));
+    add(&conn, &alice, entry("Bank", "alice99", "bank-secret"));
+
+    let accounts = alice.get_accounts(&conn).unwrap();
+    let read: Vec<(&str, &str, &str)> = accounts.iter()
+        .map(|account| (account.account.as_str(), account.username.as_str(), account.password.expose()))
+        .collect();
+
+    assert_eq!(read, [("GitHub", "alice", "gh-secret"), ("Bank", "alice99", "bank-secret")]);
+    assert!(accounts.iter().all(|account| account.accountId.is_some()));
+}
+
+#[test]
+fn debug_output_hides_passwords() {
+    let entry = entry("GitHub", "alice", "gh-secret");
+
+    assert!(!format!("{:?}", entry).contains("gh-secret"));
+    assert!(format!("{:?}", entry).contains("alice"));
+}
+
+#[test]
+fn passwords_are_not_stored_in_plaintext() {
+    let conn = seeded_vault();
+    let alice = log_in(&conn, ALICE);
+    add(&conn, &alice, entry("GitHub", "alice", "gh-secret"));
+
+    let stored: Vec<u8> = conn.query_row("SELECT accountPassword FROM accounts", [], |row| row.get(0)).unwrap();
+    assert!(!stored.windows(b"gh-secret".len()).any(|window| window == b"gh-secret"));
+}
+
+// each ciphertext is bound to its row, so moving one elsewhere in the db is caught
+#[test]
+fn swapped_ciphertexts_do_not_decrypt() {
+    let conn = seeded_vault();
+    let alice = log_in(&conn, ALICE);
+    add(&conn, &alice, entry("GitHub", "alice", "gh-secret"));
+    add(&conn, &alice, entry("Bank", "alice99", "bank-secret"));
+
+    conn.execute_batch("
+        UPDATE accounts SET accountPassword = (SELECT accountPassword FROM accounts WHERE accountName = 'GitHub'),
+            passwordNonce = (SELECT passwordNonce FROM accounts WHERE accountName = 'GitHub')
+        WHERE accountName = 'Bank';").unwrap();
+
+    assert!(matches!(alice.get_accounts(&conn), Err(Error::Crypto(CryptoError::Decryption))));
+}
+
+// vaults from before the key costs were stored and ciphertexts were bound to their rows
+// a new client's vault is in the current format from the start, so logging in never reseals it
+#[test]
+fn registration_stores_the_current_vault_format() {
+    let conn = seeded_vault();
+    let read_salt = || -> Option<Vec<u8>> {
+        conn.query_row("SELECT kdfSalt FROM clients WHERE username = ?", [ALICE.0], |row| row.get(0)).unwrap()
+    };
+
+    let (memory, iterations, parallelism, version): (Option<u32>, Option<u32>, Option<u32>, i64) = conn.query_row(
+        "SELECT kdfMemory, kdfIterations, kdfParallelism, cipherVersion FROM clients WHERE username = ?", [ALICE.0],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))).unwrap();
+    let current = KdfParams::CURRENT;
+    assert_eq!((memory, iterations, parallelism), (Some(current.m_cost), Some(current.t_cost), Some(current.p_cost)));
+    assert_eq!(version, 1);
+
+    let salt = read_salt();
+    assert!(salt.is_some());
+    log_in(&conn, ALICE);
+    assert_eq!(read_salt(), salt);
+}
+
+#[test]
+fn legacy_vaults_are_resealed_at_login() {
+    let conn = seeded_vault();
+    let salt = b"0123456789abcdef".to_vec();
+    let legacy_key = VaultKey::derive(ALICE.1, &salt, &KdfParams::LEGACY).unwrap();
+    let (ciphertext, nonce) = legacy_key.encrypt("gh-secret", b"").unwrap();
+    let (value, value_nonce) = legacy_key.encrypt("1234-5678", b"").unwrap();
+    conn.execute("UPDATE clients SET kdfSalt = ?, kdfMemory = NULL, kdfIterations = NULL, kdfParallelism = NULL, cipherVersion = 0
+        WHERE username = ?", rusqlite::params![salt, ALICE.0]).unwrap();
+    conn.execute("INSERT INTO accounts (accountId, clientId, accountName, accountUsername, accountPassword, passwordNonce)
+        SELECT 7, clientId, 'GitHub', 'alice', ?, ? FROM clients WHERE username = ?", rusqlite::params![ciphertext, nonce, ALICE.0]).unwrap();
+    conn.execute("INSERT INTO accountFields (accountId, fieldLabel, fieldType, fieldValue, valueNonce) VALUES (7, 'Codes', 'hidden', ?, ?)",
+        rusqlite::params![value, value_nonce]).unwrap();
+
+    let alice = log_in(&conn, ALICE);
+    let accounts = alice.get_accounts(&conn).unwrap();
+    assert_eq!(accounts[0].password.expose(), "gh-secret");
+    assert_eq!(accounts[0].fields, [field("Codes", FieldKind::Hidden, "1234-5678")]);
+
+    let (stored_salt, memory, version): (Vec<u8>, u32, i64) = conn.query_row(
+        "SELECT kdfSalt, kdfMemory, cipherVersion FROM clients WHERE username = ?", [ALICE.0],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))).unwrap();
+    assert_ne!(stored_salt, salt);
+    assert_eq!((memory, version), (KdfParams::CURRENT.m_cost, 1));
+    // and it is still readable the next time
+    assert_eq!(log_in(&conn, ALICE).get_accounts(&conn).unwrap()[0].fields.len(), 1);
+}
+
+#[test]
+fn clients_only_see_their_own_accounts() {
+    let conn = seeded_vault();
+    let alice = log_in(&conn, ALICE);
+    let bob = log_in(&conn, BOB);
+    add(&conn, &alice, entry("GitHub", "alice", "gh-secret"));
+    add(&conn, &bob, entry("Email", "bob", "mail-secret"));
+
+    let alice_accounts: Vec<String> = alice.get_accounts(&conn).unwrap().into_iter().map(|account| account.account).collect();
+    let bob_accounts: Vec<String> = bob.get_accounts(&conn).unwrap().into_iter().map(|account| account.account).collect();
+
+    assert_eq!(alice_accounts, ["GitHub"]);
+    assert_eq!(bob_accounts, ["Email"]);
+}
+
+#[test]
+fn clients_cannot_change_each_others_accounts() {
+    let conn = seeded_vault();
+    let alice = log_in(&conn, ALICE);
+    let bob = log_in(&conn, BOB);
+    add(&conn, &alice, entry("GitHub", "alice", "gh-secret"));
+    let alice_id = alice.get_accounts(&conn).unwrap()[0].accountId.unwrap();
+
+    let mut hijacked = entry("GitHub", "bob", "bobs-now");
+    hijacked.accountId = Some(alice_id);
+    let bob_key = bob.get_vault_key();
+
+    assert!(matches!(AccountInfo::update_account(&conn, hijacked, &bob.get_id(), bob_key), Err(Error::NotFound(_))));
+    assert!(matches!(AccountInfo::delete_account(&conn, &alice_id, &bob.get_id()), Err(Error::NotFound(_))));
+
+    let accounts = alice.get_accounts(&conn).unwrap();
+    assert_eq!((accounts[0].username.as_str(), accounts[0].password.expose()), ("alice", "gh-secret"));
+}
+
+#[test]
+fn deleted_accounts_can_be_restored() {
+    let conn = seeded_vault();
+    let alice = log_in(&conn, ALICE);
+    let bob = log_in(&conn, BOB);
+    add(&conn, &alice, entry("GitHub", "alice", "gh-secret"));
+    let account_id = alice.get_accounts(&conn).unwrap()[0].accountId.unwrap();
+
+    AccountInfo::delete_account(&conn, &account_id, &alice.get_id()).unwrap();
+    assert!(alice.get_accounts(&conn).unwrap().is_empty());
+    assert!(bob.get_trash(&conn).unwrap().is_empty());
+    assert!(matches!(AccountInfo::restore_account(&conn, &account_id, &bob.get_id()), Err(Error::NotFound(what)) if what == "entry in the trash"));
+    // it is no longer a live entry, so it cannot be deleted again
+    assert!(matches!(AccountInfo::delete_account(&conn, &account_id, &alice.get_id()), Err(Error::NotFound(what)) if what == "entry"));
+
+    AccountInfo::restore_account(&conn, &account_id, &alice.get_id()).unwrap();
+    assert_eq!(alice.get_accounts(&conn).unwrap()[0].accountId, Some(account_id));
+}
+
+fn field(label: &str, kind: FieldKind, value: &str) -> CustomField {
+    CustomField::new(label, kind, Secret::from(value)).expect("the field is not valid")
+}
+
+fn field_rows(conn: &Connection) -> i64 {
+    conn.query_row("SELECT COUNT(*) FROM accountFields", [], |row| row.get(0)).unwrap()
+}
+
+#[test]
+fn custom_fields_are_stored_encrypted() {
+    let conn = seeded_vault();
+    let alice = log_in(&conn, ALICE);
+    let mut github = entry("GitHub", "alice", "gh-secret");
+    github.fields = vec![field("", FieldKind::Url, "github.com"), field("Recovery codes", FieldKind::Hidden, "1234-5678")];
+    add(&conn, &alice, github);
+
+    let mut stored = alice.get_accounts(&conn).unwrap().remove(0);
+    assert_eq!(stored.fields, [field("URL", FieldKind::Url, "https://github.com"), field("Recovery codes", FieldKind::Hidden, "1234-5678")]);
+    let values: Vec<Vec<u8>> = conn.prepare("SELECT fieldValue FROM accountFields").unwrap()
+        .query_map([], |row| row.get(0)).unwrap().collect::<rusqlite::Result<_>>().unwrap();
+    assert!(values.iter().all(|value| !value.windows(b"1234-5678".len()).any(|window| window == b"1234-5678")));
+
+    // updating an entry replaces all of its fields
+    stored.fields = vec![field("Notes", FieldKind::Text, "shared w/bob")];
+    AccountInfo::update_account(&conn, stored, &alice.get_id(), alice.get_vault_key()).unwrap();
+    assert_eq!(alice.get_accounts(&conn).unwrap()[0].fields, [field("Notes", FieldKind::Text, "shared w/bob")]);
+    assert_eq!(field_rows(&conn), 1);
+}
+
+#[test]
+fn custom_fields_follow_their_entry_through_the_trash() {
+    let conn = seeded_vault();
+    let alice = log_in(&conn, ALICE);
+    let bob = log_in(&conn, BOB);
+    let mut github = entry("GitHub", "alice", "gh-secret");
+    github.fields = vec![field("Email", FieldKind::Email, "alice@example.com")];
+    add(&conn, &alice, github);
+    let account_id = alice.get_accounts(&conn).unwrap()[0].id().unwrap();
+
+    // another client cannot replace the fields along w/the rest of the entry
+    let mut hijacked = entry("GitHub", "bob", "bobs-now");
+    hijacked.accountId = Some(account_id);
+    assert!(AccountInfo::update_account(&conn, hijacked, &bob.get_id(), bob.get_vault_key()).is_err());
+
+    AccountInfo::delete_account(&conn, &account_id, &alice.get_id()).unwrap();
+    assert_eq!(alice.get_trash(&conn).unwrap()[0].entry.fields.len(), 1);
+    AccountInfo::restore_account(&conn, &account_id, &alice.get_id()).unwrap();
+    assert_eq!(alice.get_accounts(&conn).unwrap()[0].fields, [field("Email", FieldKind::Email, "alice@example.com")]);
+
+    AccountInfo::delete_account(&conn, &account_id, &alice.get_id()).unwrap();
+    assert!(AccountInfo::purge_account(&conn, &account_id, &bob.get_id()).is_err());
+    assert_eq!(field_rows(&conn), 1);
+    AccountInfo::purge_account(&conn, &account_id, &alice.get_id()).unwrap();
+    assert_eq!(field_rows(&conn), 0);
+}
+
+#[test]
+fn find_accounts_ranks_matches() {
+    let conn = seeded_vault();
+    let alice = log_in(&conn, ALICE);
+    let bob = log_in(&conn, BOB);
+    for (account, username) in [("Gitea", "git"), ("My GitHub", "alice"), ("Bank", "github-bot"), ("GitHub", "alice"), ("Gmail", "alice")] {
+        add(&conn, &alice, entry(account, username, "secret"));
+    }
+    add(&conn, &bob, entry("GitHub", "bob", "secret"));
+
+    let names = |query: &str| -> Vec<String> {
+        alice.find_accounts(&conn, query).unwrap().into_iter().map(|account| account.account).collect()
+    };
+
+    // exact name, then names starting w/it, then containing it, then usernames
+    assert_eq!(names("github"), ["GitHub", "My GitHub", "Bank"]);
+    // letters in order w/gaps only match fuzzily, so shorter names come first
+    assert_eq!(names("gthb"), ["Bank", "GitHub", "My GitHub"]);
+    assert_eq!(names("gmail"), ["Gmail"]);
+    assert!(names("zzz").is_empty());
+    assert_eq!(names("").len(), 5);
+}
+
+#[test]
+fn find_accounts_treats_wildcards_literally() {
+    let conn = seeded_vault();
+    let alice = log_in(&conn, ALICE);
+    add(&conn, &alice, entry("Shop", "alice", "secret"));
+    add(&conn, &alice, entry("50% off", "alice", "secret"));
+
+    let found: Vec<String> = alice.find_accounts(&conn, "%").unwrap().into_iter().map(|account| account.account).collect();
+    assert_eq!(found, ["50% off"]);
+}
+
+#[test]
+fn log_in_refuses_wrong_passwords() {
+    let conn = seeded_vault();
+
+    assert!(matches!(User::log_in(&conn, ALICE.0, BOB.1), Err(Error::BadCredentials)));
+    assert!(matches!(User::log_in(&conn, "nobody@example.com", ALICE.1), Err(Error::BadCredentials)));
+}
+
+// client ids used to be stored in a u8, which broke past the 255th client
+#[test]
+fn client_ids_are_not_limited_to_255() {
+    let conn = vault();
+    conn.execute("INSERT INTO sqlite_sequence (name, seq) VALUES ('clients', 100000)", []).unwrap();
+    let client_id = User::register_user(&conn, ALICE.0, ALICE.1).unwrap();
+
+    let alice = log_in(&conn, ALICE);
+    add(&conn, &alice, entry("GitHub", "alice", "gh-secret"));
+
+    assert_eq!(client_id, ClientId(100001));
+    assert_eq!(alice.get_id(), client_id);
+    assert_eq!(alice.get_accounts(&conn).unwrap()[0].account, "GitHub");
+}
+
+// vaults other than the default one are only ever made by create_vault
+#[test]
+fn missing_vaults_are_not_created_by_opening_them() {
+    let dir = std::env::temp_dir().join(format!("password_manager_records_missing_{}", std::process::id()));
+    let path = dir.join("typo.db");
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert!(matches!(records::open_vault(&path), Err(Error::NotFound(_))));
+    assert!(!path.exists());
+
+    records::create_vault(&path).expect("the vault was not created");
+    assert!(records::open_vault(&path).is_ok());
+    let _ = std::fs::remove_dir_all(&dir);
+}